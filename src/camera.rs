@@ -0,0 +1,63 @@
+use bevy::prelude::*;
+use bevy_dolly::prelude::*;
+
+use crate::{player::Player, AfterPhysics, GameState};
+
+pub struct CameraPlugin;
+
+#[derive(Component)]
+pub struct MainCamera;
+
+const CAMERA_OFFSET: Vec3 = Vec3::new(0., 10., 6.);
+
+impl Plugin for CameraPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnExit(GameState::Loading), setup_camera)
+            .add_systems(
+                PostUpdate,
+                update_camera
+                    .in_set(AfterPhysics)
+                    .run_if(in_state(GameState::Playing)),
+            )
+            .add_systems(
+                PostUpdate,
+                Dolly::<MainCamera>::update_active
+                    .in_set(AfterPhysics)
+                    .in_set(DollyUpdateSet)
+                    .after(update_camera),
+            );
+    }
+}
+
+fn setup_camera(mut commands: Commands) {
+    commands.spawn((
+        MainCamera,
+        Rig::builder()
+            .with(Position::new(Vec3::ZERO))
+            .with(Smooth::new_position(0.25))
+            .with(Arm::new(CAMERA_OFFSET))
+            .with(Smooth::new_position(0.25))
+            .with(LookAt::new(Vec3::ZERO + Vec3::Y).tracking_smoothness(0.25))
+            .build(),
+        Camera3d::default(),
+        Camera {
+            clear_color: ClearColorConfig::None,
+            ..default()
+        },
+        Transform::from_translation(CAMERA_OFFSET).looking_at(Vec3::ZERO, Vec3::Y),
+    ));
+}
+
+fn update_camera(player_query: Query<&Transform, With<Player>>, mut rig_query: Query<&mut Rig>) {
+    let Ok(player) = player_query.get_single() else {
+        return;
+    };
+
+    let Ok(mut rig) = rig_query.get_single_mut() else {
+        return;
+    };
+
+    rig.driver_mut::<Position>().position = player.translation;
+    // rig.driver_mut::<Rotation>().rotation = player.rotation;
+    rig.driver_mut::<LookAt>().target = player.translation + Vec3::Y;
+}
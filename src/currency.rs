@@ -0,0 +1,55 @@
+use bevy::prelude::*;
+
+use crate::{
+    damage::{self, EnemyKilled},
+    map::Item,
+    GameState,
+};
+
+pub struct CurrencyPlugin;
+
+/// Coins earned by killing enemies, which can be spent to skip an item spawner's timer.
+#[derive(Resource, Reflect, Default)]
+#[reflect(Resource)]
+pub struct Currency(pub u32);
+
+impl Plugin for CurrencyPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Currency>()
+            .register_type::<Currency>()
+            .add_systems(
+                FixedUpdate,
+                earn.run_if(in_state(GameState::Playing))
+                    .after(damage::resolve),
+            )
+            .add_systems(OnExit(GameState::GameOver), reset)
+            .add_systems(
+                OnExit(GameState::Playing),
+                reset.run_if(not(in_state(GameState::GameOver))),
+            );
+    }
+}
+
+/// Coins awarded for killing an enemy with `max_hp` hit points: one for every two hit
+/// points, rounded up, plus one so that even the weakest enemies are worth a little.
+pub fn bounty(max_hp: u32) -> u32 {
+    max_hp.div_ceil(2) + 1
+}
+
+/// Coins it costs to spawn `item` without waiting for its spawner.
+pub fn price(item: Item) -> u32 {
+    match item {
+        Item::TowerKit(_) => 25,
+        Item::LaserAmmo => 2,
+    }
+}
+
+fn earn(mut events: EventReader<EnemyKilled>, mut currency: ResMut<Currency>) {
+    for event in events.read() {
+        currency.0 += bounty(event.max_hp);
+    }
+}
+
+fn reset(mut commands: Commands) {
+    commands.insert_resource(Currency::default());
+}
@@ -0,0 +1,299 @@
+use bevy::prelude::*;
+
+use crate::{
+    enemy::{Armor, Enemy, EnemyKind, HitPoints, WaveIndex},
+    stats::BuildOrder,
+    status::{StatusEffect, StatusEffects},
+    GameState,
+};
+
+/// Multiplier applied to the damage of a critical hit.
+pub const CRIT_MULTIPLIER: u32 = 2;
+
+pub struct DamagePlugin;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DamageKind {
+    #[default]
+    Kinetic,
+    Energy,
+    Fire,
+}
+
+/// The fraction of each kind of damage that an enemy shrugs off. Negative values make
+/// the enemy take extra damage of that kind.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Resistances {
+    pub kinetic: f32,
+    pub energy: f32,
+    pub fire: f32,
+}
+impl Resistances {
+    pub fn get(&self, kind: DamageKind) -> f32 {
+        match kind {
+            DamageKind::Kinetic => self.kinetic,
+            DamageKind::Energy => self.energy,
+            DamageKind::Fire => self.fire,
+        }
+    }
+}
+
+/// Sent to hit an enemy. Armor, resistances and status effects are applied when the
+/// damage is resolved, and the hit's own effects only take hold after that.
+#[derive(Event, Clone, Debug)]
+pub struct Damage {
+    pub target: Entity,
+    pub amount: u32,
+    pub kind: DamageKind,
+    /// The tower responsible, if any.
+    pub source: Option<BuildOrder>,
+    pub crit: bool,
+    /// Effects applied to the target, even if its armor absorbs the hit.
+    pub effects: Vec<StatusEffect>,
+}
+
+/// Sent when an enemy loses hit points.
+#[derive(Event, Clone, Debug)]
+pub struct EnemyDamaged {
+    pub enemy: Entity,
+    /// Hit points lost, which can be less than the damage dealt if the enemy had fewer
+    /// left.
+    pub amount: u32,
+    pub kind: DamageKind,
+    pub source: Option<BuildOrder>,
+    pub crit: bool,
+}
+
+/// Sent when an enemy runs out of hit points. The enemy is despawned afterwards.
+#[derive(Event, Clone, Debug)]
+pub struct EnemyKilled {
+    pub enemy: Entity,
+    pub kind: EnemyKind,
+    pub max_hp: u32,
+    /// Index of the wave that the enemy belonged to.
+    pub wave: usize,
+    /// The tower that dealt the final blow, if any.
+    pub source: Option<BuildOrder>,
+}
+
+impl Plugin for DamagePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<Damage>()
+            .add_event::<EnemyDamaged>()
+            .add_event::<EnemyKilled>()
+            .add_systems(FixedUpdate, resolve.run_if(in_state(GameState::Playing)));
+    }
+}
+
+/// Returns the hit points that an enemy loses to a hit, ignoring its armor. Fractions
+/// of a hit point are carried over between hits by `HitPoints::partial`.
+pub fn mitigated(
+    amount: u32,
+    kind: DamageKind,
+    crit: bool,
+    resistances: &Resistances,
+    effects: &StatusEffects,
+) -> f32 {
+    let amount = if crit {
+        amount * CRIT_MULTIPLIER
+    } else {
+        amount
+    };
+
+    let multiplier = (1. - resistances.get(kind)).max(0.) * effects.damage_taken_multiplier();
+
+    amount as f32 * multiplier
+}
+
+/// Applies all of the damage sent this tick.
+pub fn resolve(
+    mut events: EventReader<Damage>,
+    mut query: Query<
+        (
+            &mut HitPoints,
+            &mut Armor,
+            &mut StatusEffects,
+            &EnemyKind,
+            &WaveIndex,
+        ),
+        With<Enemy>,
+    >,
+    mut damaged_events: EventWriter<EnemyDamaged>,
+    mut killed_events: EventWriter<EnemyKilled>,
+) {
+    for event in events.read() {
+        let Ok((mut hp, mut armor, mut effects, kind, wave)) = query.get_mut(event.target) else {
+            continue;
+        };
+
+        // Already killed by an earlier hit this tick.
+        if hp.current == 0 {
+            continue;
+        }
+
+        let before = hp.current;
+        // Shots that only apply effects don't wear down armor.
+        if event.amount > 0 && armor.0 > 0 && !effects.armor_broken() {
+            armor.0 -= 1;
+        } else {
+            let amount = hp.partial
+                + mitigated(
+                    event.amount,
+                    event.kind,
+                    event.crit,
+                    &kind.stats().resistances,
+                    &effects,
+                );
+            hp.partial = amount.fract();
+            hp.current = hp.current.saturating_sub(amount as u32);
+        }
+
+        // Applied after the hit, so that a shot which breaks armor doesn't get through
+        // it or deal extra damage itself.
+        for effect in &event.effects {
+            effects.apply(effect, event.source);
+        }
+
+        if hp.current == before {
+            continue;
+        }
+
+        damaged_events.send(EnemyDamaged {
+            enemy: event.target,
+            amount: before - hp.current,
+            kind: event.kind,
+            source: event.source,
+            crit: event.crit,
+        });
+
+        if hp.current == 0 {
+            killed_events.send(EnemyKilled {
+                enemy: event.target,
+                kind: *kind,
+                max_hp: hp.max,
+                wave: wave.0,
+                source: event.source,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::status::EffectKind;
+
+    fn app() -> App {
+        let mut app = App::new();
+        app.add_event::<Damage>()
+            .add_event::<EnemyDamaged>()
+            .add_event::<EnemyKilled>()
+            .add_systems(Update, resolve);
+        app
+    }
+
+    fn spawn_enemy(app: &mut App, kind: EnemyKind, armor: u32) -> Entity {
+        app.world_mut()
+            .spawn((
+                Enemy,
+                HitPoints {
+                    current: 20,
+                    max: 20,
+                    partial: 0.,
+                },
+                Armor(armor),
+                StatusEffects::default(),
+                kind,
+                WaveIndex(0),
+            ))
+            .id()
+    }
+
+    /// Hits the enemy and returns the hit points it has left.
+    fn hit(app: &mut App, enemy: Entity, damage: Damage) -> u32 {
+        app.world_mut().send_event(Damage {
+            target: enemy,
+            ..damage
+        });
+        app.update();
+        app.world().get::<HitPoints>(enemy).unwrap().current
+    }
+
+    fn damage(amount: u32, kind: DamageKind) -> Damage {
+        Damage {
+            target: Entity::PLACEHOLDER,
+            amount,
+            kind,
+            source: None,
+            crit: false,
+            effects: vec![],
+        }
+    }
+
+    #[test]
+    fn resistances_and_crits() {
+        let effects = StatusEffects::default();
+        let resistances = Resistances {
+            kinetic: 0.5,
+            energy: 0.,
+            fire: -1.,
+        };
+
+        assert_eq!(
+            mitigated(4, DamageKind::Kinetic, false, &resistances, &effects),
+            2.
+        );
+        assert_eq!(
+            mitigated(4, DamageKind::Kinetic, true, &resistances, &effects),
+            4.
+        );
+        assert_eq!(
+            mitigated(4, DamageKind::Energy, false, &resistances, &effects),
+            4.
+        );
+        assert_eq!(
+            mitigated(4, DamageKind::Fire, false, &resistances, &effects),
+            8.
+        );
+    }
+
+    #[test]
+    fn resistances_count_against_one_damage_hits() {
+        let mut app = app();
+
+        // Armored enemies shrug off half of kinetic damage.
+        let armored = spawn_enemy(&mut app, EnemyKind::Armored, 0);
+        assert_eq!(hit(&mut app, armored, damage(1, DamageKind::Kinetic)), 20);
+        assert_eq!(hit(&mut app, armored, damage(1, DamageKind::Kinetic)), 19);
+        assert_eq!(hit(&mut app, armored, damage(1, DamageKind::Kinetic)), 19);
+        assert_eq!(hit(&mut app, armored, damage(1, DamageKind::Kinetic)), 18);
+
+        // Tanks shrug off a quarter of energy damage.
+        let tank = spawn_enemy(&mut app, EnemyKind::Tank, 0);
+        for _ in 0..4 {
+            hit(&mut app, tank, damage(1, DamageKind::Energy));
+        }
+        assert_eq!(app.world().get::<HitPoints>(tank).unwrap().current, 17);
+    }
+
+    #[test]
+    fn effects_apply_after_the_hit() {
+        let mut app = app();
+        let enemy = spawn_enemy(&mut app, EnemyKind::Basic, 1);
+
+        let armor_break = || Damage {
+            effects: vec![StatusEffect {
+                kind: EffectKind::ArmorBreak,
+                duration: 10.,
+            }],
+            ..damage(4, DamageKind::Kinetic)
+        };
+
+        // The armor absorbs the hit that breaks it.
+        assert_eq!(hit(&mut app, enemy, armor_break()), 20);
+        assert_eq!(app.world().get::<Armor>(enemy).unwrap().0, 0);
+
+        // Later hits take the break into account.
+        assert_eq!(hit(&mut app, enemy, armor_break()), 14);
+    }
+}
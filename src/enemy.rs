@@ -1,11 +1,19 @@
-use bevy::{audio::Volume, math::Vec3Swizzles, prelude::*};
+use std::fmt::Display;
+
+use bevy::{audio::Volume, math::Vec3Swizzles, prelude::*, utils::HashMap};
 use bevy_rapier3d::prelude::*;
+use serde::Deserialize;
 
 use crate::{
+    damage::{self, EnemyKilled, Resistances},
+    level::Level,
+    lives::Lives,
     loading::{Models, Sounds},
-    map::{map_to_world, PATH},
+    map::map_to_world,
     settings::SfxSetting,
-    DespawnOnReset, GameState, Lives,
+    stats::RunStats,
+    status::{EffectIndicator, StatusEffects},
+    DespawnOnReset, GameState,
 };
 
 pub struct EnemyPlugin;
@@ -13,49 +21,251 @@ pub struct EnemyPlugin;
 #[derive(Component)]
 pub struct PathIndex(pub usize);
 
+/// Index of the wave that an enemy belongs to.
+#[derive(Component)]
+pub struct WaveIndex(pub usize);
+
+/// How far an enemy is along the path, in world units.
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq)]
+pub struct PathProgress {
+    /// Distance travelled from the enemy spawn.
+    pub travelled: f32,
+    /// Distance left until the goal.
+    pub remaining: f32,
+}
+impl PathProgress {
+    /// Computes the progress of an enemy at `translation`, given the index of the last
+    /// waypoint that it reached.
+    pub fn new(level: &Level, path_index: usize, translation: Vec3) -> Self {
+        let mut travelled = 0.;
+        let mut remaining = 0.;
+        for (i, window) in level.path.windows(2).enumerate() {
+            let len = map_to_world(level, window[0]).distance(map_to_world(level, window[1]));
+            if i < path_index {
+                travelled += len;
+            } else {
+                remaining += len;
+            }
+        }
+
+        // Enemies move in a straight line from the last waypoint they reached.
+        let waypoint = map_to_world(level, level.path[path_index]);
+        let along_segment = translation.xz().distance(waypoint.xz());
+        travelled += along_segment;
+        remaining -= along_segment;
+
+        Self {
+            travelled,
+            remaining: remaining.max(0.),
+        }
+    }
+}
+
 #[derive(Component)]
 pub struct Enemy;
 
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Deserialize)]
+pub enum EnemyKind {
+    #[default]
+    Basic,
+    /// Fast and fragile.
+    Fast,
+    /// Slow, tanky, and costs an extra life if it gets through.
+    Tank,
+    /// Ignores the first few hits it takes.
+    Armored,
+    /// Small and weak, meant to be spawned in large numbers.
+    Swarm,
+}
+impl EnemyKind {
+    pub fn stats(&self) -> EnemyStats {
+        match self {
+            Self::Basic => EnemyStats {
+                speed: 1.,
+                radius: 0.5,
+                hp_multiplier: 1.,
+                armor: 0,
+                lives_cost: 1,
+                resistances: Resistances::default(),
+            },
+            Self::Fast => EnemyStats {
+                speed: 2.,
+                radius: 0.35,
+                hp_multiplier: 0.5,
+                armor: 0,
+                lives_cost: 1,
+                resistances: Resistances::default(),
+            },
+            Self::Tank => EnemyStats {
+                speed: 0.6,
+                radius: 0.7,
+                hp_multiplier: 3.,
+                armor: 0,
+                lives_cost: 2,
+                resistances: Resistances {
+                    kinetic: 0.,
+                    energy: 0.25,
+                    fire: 0.5,
+                },
+            },
+            Self::Armored => EnemyStats {
+                speed: 0.9,
+                radius: 0.5,
+                hp_multiplier: 1.,
+                armor: 3,
+                lives_cost: 1,
+                resistances: Resistances {
+                    kinetic: 0.5,
+                    energy: -0.5,
+                    fire: 0.,
+                },
+            },
+            Self::Swarm => EnemyStats {
+                speed: 1.4,
+                radius: 0.3,
+                hp_multiplier: 0.35,
+                armor: 0,
+                lives_cost: 1,
+                resistances: Resistances {
+                    kinetic: 0.,
+                    energy: 0.,
+                    fire: -1.,
+                },
+            },
+        }
+    }
+
+    /// Returns the hit points that an enemy of this kind spawns with, given the hit
+    /// points from the wave data.
+    pub fn hp(&self, base: u32) -> u32 {
+        ((base as f32 * self.stats().hp_multiplier).round() as u32).max(1)
+    }
+
+    /// Color that the model's materials are multiplied by, if any. Every kind shares the
+    /// one model, so they're told apart by this and their size.
+    pub fn tint(&self) -> Option<Color> {
+        match self {
+            Self::Basic => None,
+            Self::Fast => Some(Color::srgb(1.0, 0.9, 0.3)),
+            Self::Tank => Some(Color::srgb(1.0, 0.35, 0.35)),
+            Self::Armored => Some(Color::srgb(0.55, 0.65, 0.9)),
+            Self::Swarm => Some(Color::srgb(0.45, 1.0, 0.45)),
+        }
+    }
+}
+impl Display for EnemyKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Basic => "BASIC",
+                Self::Fast => "FAST",
+                Self::Tank => "TANK",
+                Self::Armored => "ARMORED",
+                Self::Swarm => "SWARM",
+            }
+        )
+    }
+}
+
+pub struct EnemyStats {
+    /// Movement speed, in world units per second.
+    pub speed: f32,
+    pub radius: f32,
+    pub hp_multiplier: f32,
+    /// Number of hits that are absorbed before the enemy starts losing hit points.
+    pub armor: u32,
+    /// Number of lives lost when the enemy reaches the end of the path.
+    pub lives_cost: u32,
+    pub resistances: Resistances,
+}
+
+/// The radius of the enemy model at a scale of 1.
+const MODEL_RADIUS: f32 = 0.5;
+
+/// Remaining hits that will be absorbed without losing any hit points.
+#[derive(Component)]
+pub struct Armor(pub u32);
+
 #[derive(Component)]
 pub struct HitPoints {
     pub current: u32,
-    #[allow(dead_code)]
     pub max: u32,
+    /// Damage taken that hasn't added up to a whole hit point yet, so that resistances
+    /// still count against small hits.
+    pub partial: f32,
 }
 impl HitPoints {
     /// Creates a new `HitPoints`, starting with full health.
     fn new(max: u32) -> Self {
-        Self { current: max, max }
+        Self {
+            current: max,
+            max,
+            partial: 0.,
+        }
     }
 }
 
 #[derive(Event)]
 pub struct SpawnEnemyEvent {
+    pub kind: EnemyKind,
     pub hp: u32,
+    /// Index of the wave that the enemy belongs to.
+    pub wave: usize,
 }
 
+/// Sent when an enemy makes it to the end of the path and costs the player lives.
+#[derive(Event)]
+pub struct EnemyReachedGoalEvent(pub EnemyKind);
+
 impl Plugin for EnemyPlugin {
     fn build(&self, app: &mut App) {
         app.add_event::<SpawnEnemyEvent>()
-            .add_systems(Update, spawn.run_if(in_state(GameState::Playing)))
-            .add_systems(Update, movement.run_if(in_state(GameState::Playing)))
-            .add_systems(Update, death.run_if(in_state(GameState::Playing)));
+            .add_event::<EnemyReachedGoalEvent>()
+            .add_systems(FixedUpdate, spawn.run_if(in_state(GameState::Playing)))
+            .add_systems(FixedUpdate, movement.run_if(in_state(GameState::Playing)))
+            .add_systems(
+                FixedUpdate,
+                death
+                    .run_if(in_state(GameState::Playing))
+                    .after(damage::resolve),
+            );
     }
 }
 
-fn spawn(mut commands: Commands, models: Res<Models>, mut events: EventReader<SpawnEnemyEvent>) {
+/// Models and sounds for enemies, which aren't needed to simulate the game.
+pub struct EnemyPresentationPlugin;
+
+impl Plugin for EnemyPresentationPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, spawn_model.run_if(in_state(GameState::Playing)))
+            .add_systems(Update, tint_model.run_if(in_state(GameState::Playing)))
+            .add_systems(Update, damage_sound.run_if(in_state(GameState::Playing)));
+    }
+}
+
+fn spawn(mut commands: Commands, mut events: EventReader<SpawnEnemyEvent>, level: Res<Level>) {
     for event in events.read() {
+        let stats = event.kind.stats();
+
         commands.spawn((
             Enemy,
+            event.kind,
             Name::new("Enemy"),
-            SceneRoot(models.enemy1.clone()),
-            Transform::from_translation(map_to_world(PATH[0])),
-            Collider::ball(0.5),
+            // The collider is scaled along with the model.
+            Transform::from_translation(map_to_world(&level, level.path[0]))
+                .with_scale(Vec3::splat(stats.radius / MODEL_RADIUS)),
+            Collider::ball(MODEL_RADIUS),
             ActiveEvents::COLLISION_EVENTS,
             ActiveCollisionTypes::STATIC_STATIC,
             Sensor,
             PathIndex(0),
-            HitPoints::new(event.hp),
+            WaveIndex(event.wave),
+            PathProgress::new(&level, 0, map_to_world(&level, level.path[0])),
+            HitPoints::new(event.kind.hp(event.hp)),
+            Armor(stats.armor),
+            StatusEffects::default(),
             DespawnOnReset,
         ));
     }
@@ -63,20 +273,38 @@ fn spawn(mut commands: Commands, models: Res<Models>, mut events: EventReader<Sp
 
 fn movement(
     mut commands: Commands,
-    mut query: Query<(Entity, &mut Transform, &mut PathIndex), With<Enemy>>,
+    mut query: Query<
+        (
+            Entity,
+            &mut Transform,
+            &mut PathIndex,
+            &mut PathProgress,
+            &EnemyKind,
+            &WaveIndex,
+            &StatusEffects,
+        ),
+        With<Enemy>,
+    >,
     time: Res<Time>,
     mut lives: ResMut<Lives>,
-    game_audio: Res<Sounds>,
-    audio_setting: Res<SfxSetting>,
+    mut run_stats: ResMut<RunStats>,
+    mut events: EventWriter<EnemyReachedGoalEvent>,
+    level: Res<Level>,
 ) {
-    for (entity, mut transform, mut path_index) in query.iter_mut() {
-        if let Some(next_waypoint) = PATH.get(path_index.0 + 1) {
-            let world = map_to_world(*next_waypoint);
+    for (entity, mut transform, mut path_index, mut progress, kind, wave, effects) in
+        query.iter_mut()
+    {
+        let stats = kind.stats();
+
+        let speed = stats.speed * effects.speed_multiplier();
+
+        if let Some(next_waypoint) = level.path.get(path_index.0 + 1) {
+            let world = map_to_world(&level, *next_waypoint);
 
             let diff = world - transform.translation;
             let dist = diff.length();
 
-            let step = 1. * time.delta_secs();
+            let step = speed * time.delta_secs();
 
             let diff_xz = diff.xz();
             transform.rotation = Quat::from_rotation_y(diff_xz.angle_to(Vec2::Y));
@@ -90,22 +318,186 @@ fn movement(
                 transform.translation.z = world.z;
                 path_index.0 += 1;
             }
+
+            *progress = PathProgress::new(&level, path_index.0, transform.translation);
         } else {
-            commands.spawn((
-                AudioPlayer(game_audio.damage.clone()),
-                PlaybackSettings::DESPAWN.with_volume(Volume::new(**audio_setting as f32 / 100.)),
-            ));
+            events.send(EnemyReachedGoalEvent(*kind));
 
-            lives.0 = lives.0.saturating_sub(1);
+            lives.0 = lives.0.saturating_sub(stats.lives_cost);
+            run_stats.wave_mut(wave.0).leaked += 1;
             commands.entity(entity).despawn_recursive();
         }
     }
 }
 
-fn death(mut commands: Commands, query: Query<(Entity, &HitPoints), With<Enemy>>) {
-    for (entity, hp) in query.iter() {
-        if hp.current == 0 {
-            commands.entity(entity).despawn_recursive();
+fn spawn_model(mut commands: Commands, query: Query<Entity, Added<Enemy>>, models: Res<Models>) {
+    for entity in query.iter() {
+        commands
+            .entity(entity)
+            .insert(SceneRoot(models.enemy1.clone()));
+    }
+}
+
+/// Swaps the materials of each enemy's model, once it has loaded, for ones tinted to
+/// match its kind.
+fn tint_model(
+    mut commands: Commands,
+    query: Query<
+        (Entity, &MeshMaterial3d<StandardMaterial>),
+        (
+            Added<MeshMaterial3d<StandardMaterial>>,
+            Without<EffectIndicator>,
+        ),
+    >,
+    parent_query: Query<&Parent>,
+    kind_query: Query<&EnemyKind, With<Enemy>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut tinted: Local<HashMap<(EnemyKind, AssetId<StandardMaterial>), Handle<StandardMaterial>>>,
+) {
+    for (entity, material) in query.iter() {
+        let Some(kind) = parent_query
+            .iter_ancestors(entity)
+            .find_map(|ancestor| kind_query.get(ancestor).ok())
+        else {
+            continue;
+        };
+
+        let Some(tint) = kind.tint() else {
+            continue;
+        };
+
+        let key = (*kind, material.0.id());
+        let handle = match tinted.get(&key) {
+            Some(handle) => handle.clone(),
+            None => {
+                let Some(original) = materials.get(&material.0) else {
+                    continue;
+                };
+
+                let base = original.base_color.to_linear();
+                let tint = tint.to_linear();
+                let handle = materials.add(StandardMaterial {
+                    base_color: LinearRgba::new(
+                        base.red * tint.red,
+                        base.green * tint.green,
+                        base.blue * tint.blue,
+                        base.alpha,
+                    )
+                    .into(),
+                    ..original.clone()
+                });
+                tinted.insert(key, handle.clone());
+                handle
+            }
+        };
+
+        commands.entity(entity).insert(MeshMaterial3d(handle));
+    }
+}
+
+fn damage_sound(
+    mut commands: Commands,
+    mut events: EventReader<EnemyReachedGoalEvent>,
+    game_audio: Res<Sounds>,
+    audio_setting: Res<SfxSetting>,
+) {
+    for _ in events.read() {
+        commands.spawn((
+            AudioPlayer(game_audio.damage.clone()),
+            PlaybackSettings::DESPAWN.with_volume(Volume::new(**audio_setting as f32 / 100.)),
+        ));
+    }
+}
+
+fn death(mut commands: Commands, mut events: EventReader<EnemyKilled>) {
+    for event in events.read() {
+        commands.entity(event.enemy).despawn_recursive();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn level() -> Level {
+        ron::de::from_str(include_str!("../assets/levels/1.level.ron")).unwrap()
+    }
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-4, "{} != {}", a, b);
+    }
+
+    /// The length of the first level's path: 31 tiles, 2 world units each.
+    const PATH_LENGTH: f32 = 62.;
+
+    #[test]
+    fn progress_at_spawn() {
+        let level = level();
+        let progress = PathProgress::new(&level, 0, map_to_world(&level, level.path[0]));
+
+        assert_close(progress.travelled, 0.);
+        assert_close(progress.remaining, PATH_LENGTH);
+    }
+
+    #[test]
+    fn progress_at_goal() {
+        let level = level();
+        let last = level.path.len() - 1;
+        let progress = PathProgress::new(&level, last, map_to_world(&level, level.path[last]));
+
+        assert_close(progress.travelled, PATH_LENGTH);
+        assert_close(progress.remaining, 0.);
+    }
+
+    #[test]
+    fn progress_is_continuous_at_corners() {
+        let level = level();
+
+        for i in 1..level.path.len() - 1 {
+            let corner = map_to_world(&level, level.path[i]);
+
+            // Arriving at the corner and leaving it should agree
+            let arriving = PathProgress::new(&level, i - 1, corner);
+            let leaving = PathProgress::new(&level, i, corner);
+
+            assert_close(arriving.travelled, leaving.travelled);
+            assert_close(arriving.remaining, leaving.remaining);
+        }
+    }
+
+    #[test]
+    fn progress_around_first_corner() {
+        let level = level();
+
+        // (0, 4) -> (11, 4) is 11 tiles, then (11, 4) -> (11, 10) is 6 tiles.
+        let before = map_to_world(&level, UVec2::new(10, 4));
+        let after = map_to_world(&level, UVec2::new(11, 5));
+
+        let before = PathProgress::new(&level, 0, before);
+        let after = PathProgress::new(&level, 1, after);
+
+        assert_close(before.travelled, 20.);
+        assert_close(after.travelled, 24.);
+        assert_close(after.remaining, PATH_LENGTH - 24.);
+    }
+
+    #[test]
+    fn progress_increases_along_path() {
+        let level = level();
+        let mut last = -1.;
+
+        for (i, window) in level.path.windows(2).enumerate() {
+            let start = map_to_world(&level, window[0]);
+            let end = map_to_world(&level, window[1]);
+
+            for step in 0..10 {
+                let pos = start.lerp(end, step as f32 / 10.);
+                let progress = PathProgress::new(&level, i, pos);
+
+                assert!(progress.travelled > last);
+                assert_close(progress.travelled + progress.remaining, PATH_LENGTH);
+                last = progress.travelled;
+            }
         }
     }
 }
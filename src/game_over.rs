@@ -1,16 +1,24 @@
-use bevy::prelude::*;
+use bevy::{ecs::system::SystemParam, prelude::*};
 use bevy_alt_ui_navigation_lite::prelude::*;
 
 use crate::{
-    loading::Fonts,
-    ui::{buttons, BUTTON_TEXT, CONTAINER_BACKGROUND, NORMAL_BUTTON, TITLE_TEXT},
-    GameState, Won,
+    level::{CurrentLevel, Level},
+    lives::{Lives, Won},
+    loading::{Fonts, Levels},
+    score::{self, BestWaves, HighScores, RunScore},
+    settings::DifficultySetting,
+    stats::{run_secs, RunStats},
+    ui::{
+        buttons, ALT_TEXT, BUTTON_TEXT, CONTAINER_BACKGROUND, NORMAL_BUTTON, TITLE_TEXT, UI_TEXT,
+        WARNING_TEXT,
+    },
+    GameState, RunTick, TickRate,
 };
 
 pub struct GameOverPlugin;
 impl Plugin for GameOverPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(OnEnter(GameState::GameOver), spawn)
+        app.add_systems(OnEnter(GameState::GameOver), spawn.after(score::record))
             .add_systems(
                 Update,
                 (button_actions, buttons.after(NavRequestSystem))
@@ -26,10 +34,18 @@ struct GameOverMarker;
 struct PlayAgainButton;
 #[derive(Component)]
 enum GameOverButton {
+    NextLevel,
     PlayAgain,
 }
 
-fn spawn(mut commands: Commands, fonts: Res<Fonts>, won: Res<Won>) {
+fn spawn(
+    mut commands: Commands,
+    fonts: Res<Fonts>,
+    won: Res<Won>,
+    current: Res<CurrentLevel>,
+    levels: Res<Levels>,
+    results: Results,
+) {
     let title_text_style = (
         TextFont {
             font: fonts.main.clone(),
@@ -96,6 +112,30 @@ fn spawn(mut commands: Commands, fonts: Res<Fonts>, won: Res<Won>) {
         ))
         .id();
 
+    commands.entity(root).add_children(&[container]);
+
+    commands.entity(container).add_child(title);
+
+    let results = spawn_results(&mut commands, &fonts, &results);
+    commands.entity(container).add_child(results);
+
+    if won.0 && current.0 + 1 < levels.levels.len() {
+        let next_level = commands
+            .spawn((
+                Button,
+                button_node.clone(),
+                BackgroundColor(NORMAL_BUTTON.into()),
+                Focusable::default().prioritized(),
+                GameOverButton::NextLevel,
+            ))
+            .with_children(|parent| {
+                parent.spawn((Text::new("NEXT LEVEL"), button_text_style.clone()));
+            })
+            .id();
+
+        commands.entity(container).add_child(next_level);
+    }
+
     let play_again = commands
         .spawn((
             Button,
@@ -110,20 +150,214 @@ fn spawn(mut commands: Commands, fonts: Res<Fonts>, won: Res<Won>) {
         })
         .id();
 
-    commands.entity(root).add_children(&[container]);
+    commands.entity(container).add_child(play_again);
+}
+
+#[derive(SystemParam)]
+struct Results<'w> {
+    score: Res<'w, RunScore>,
+    high_scores: Res<'w, HighScores>,
+    best_waves: Res<'w, BestWaves>,
+    stats: Res<'w, RunStats>,
+    lives: Res<'w, Lives>,
+    tick: Res<'w, RunTick>,
+    tick_rate: Res<'w, TickRate>,
+    difficulty: Res<'w, DifficultySetting>,
+    level: Res<'w, Level>,
+}
+
+/// The number of waves shown in the breakdown. Earlier waves are left out.
+const MAX_WAVE_ROWS: usize = 10;
+
+/// Spawns the run's score and statistics, the high scores for the level, and a breakdown
+/// of each wave and tower.
+fn spawn_results(commands: &mut Commands, fonts: &Fonts, results: &Results) -> Entity {
+    let text_font = TextFont {
+        font: fonts.main.clone(),
+        font_size: 16.0,
+        ..default()
+    };
+    let column_node = Node {
+        flex_direction: FlexDirection::Column,
+        width: Val::Px(220.0),
+        ..default()
+    };
+
+    let stats = &results.stats;
+    let secs = run_secs(&results.tick, &results.tick_rate) as u32;
+    let summary = [
+        ("SCORE", results.score.score.to_string()),
+        ("KILLS", stats.kills.to_string()),
+        ("LIVES", results.lives.0.to_string()),
+        ("TIME", format!("{}:{:0>2}", secs / 60, secs % 60)),
+        ("DIFFICULTY", results.difficulty.to_string()),
+    ];
+    let run = [
+        (
+            "LASERS HIT",
+            format!("{}/{}", stats.lasers_hit, stats.lasers_fired),
+        ),
+        ("AMMO DELIVERED", stats.ammo_delivered.to_string()),
+        ("TOWERS BUILT", stats.towers.len().to_string()),
+        ("FALLS", stats.falls.to_string()),
+    ];
+
+    let high_scores = results
+        .high_scores
+        .get(&results.level.name, &results.difficulty);
 
     commands
-        .entity(container)
-        .add_children(&[title, play_again]);
+        .spawn(Node {
+            column_gap: Val::Px(20.0),
+            margin: UiRect::bottom(Val::Px(10.0)),
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn(column_node.clone()).with_children(|parent| {
+                for (label, value) in summary {
+                    spawn_row(parent, &text_font, label, value, ALT_TEXT);
+                }
+
+                // Endless runs are measured by how far they got instead
+                if let Some((wave, new_best)) = results.score.endless_wave {
+                    let best = results
+                        .best_waves
+                        .get(&results.level.name, &results.difficulty)
+                        .unwrap_or(wave);
+
+                    spawn_heading(
+                        parent,
+                        &text_font,
+                        if new_best {
+                            "NEW BEST WAVE!"
+                        } else {
+                            "ENDLESS"
+                        },
+                    );
+                    spawn_row(parent, &text_font, "WAVE", wave.to_string(), ALT_TEXT);
+                    spawn_row(parent, &text_font, "BEST WAVE", best.to_string(), ALT_TEXT);
+                    return;
+                }
+
+                spawn_heading(
+                    parent,
+                    &text_font,
+                    match results.score.rank {
+                        Some(0) => "NEW HIGH SCORE!",
+                        _ => "HIGH SCORES",
+                    },
+                );
+
+                for (i, high_score) in high_scores.iter().enumerate() {
+                    // The run's own score stands out
+                    let color = if results.score.rank == Some(i) {
+                        TITLE_TEXT
+                    } else {
+                        ALT_TEXT
+                    };
+
+                    spawn_row(
+                        parent,
+                        &text_font,
+                        &format!("{}.", i + 1),
+                        high_score.to_string(),
+                        color,
+                    );
+                }
+            });
+
+            parent.spawn(column_node.clone()).with_children(|parent| {
+                for (label, value) in run {
+                    spawn_row(parent, &text_font, label, value, ALT_TEXT);
+                }
+
+                spawn_heading(parent, &text_font, "TOWER DAMAGE");
+
+                for (i, tower) in stats.towers.iter().enumerate() {
+                    spawn_row(
+                        parent,
+                        &text_font,
+                        &format!("{}. {}", i + 1, tower.kind.to_string().to_uppercase()),
+                        tower.damage.to_string(),
+                        ALT_TEXT,
+                    );
+                }
+            });
+
+            parent.spawn(column_node).with_children(|parent| {
+                spawn_row(
+                    parent,
+                    &text_font,
+                    "WAVE",
+                    "KILLED/LEAKED".to_string(),
+                    UI_TEXT,
+                );
+
+                let skip = stats.waves.len().saturating_sub(MAX_WAVE_ROWS);
+                for (i, wave) in stats.waves.iter().enumerate().skip(skip) {
+                    spawn_row(
+                        parent,
+                        &text_font,
+                        &(i + 1).to_string(),
+                        format!("{}/{}", wave.killed, wave.leaked),
+                        if wave.leaked > 0 {
+                            WARNING_TEXT
+                        } else {
+                            ALT_TEXT
+                        },
+                    );
+                }
+            });
+        })
+        .id()
+}
+
+fn spawn_row(
+    parent: &mut ChildBuilder,
+    text_font: &TextFont,
+    label: &str,
+    value: String,
+    color: Srgba,
+) {
+    parent
+        .spawn(Node {
+            justify_content: JustifyContent::SpaceBetween,
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn((
+                Text::new(label),
+                text_font.clone(),
+                TextColor(UI_TEXT.into()),
+            ));
+            parent.spawn((Text::new(value), text_font.clone(), TextColor(color.into())));
+        });
+}
+
+fn spawn_heading(parent: &mut ChildBuilder, text_font: &TextFont, text: &str) {
+    parent.spawn((
+        Text::new(text),
+        text_font.clone(),
+        TextColor(TITLE_TEXT.into()),
+        Node {
+            margin: UiRect::top(Val::Px(10.0)),
+            ..default()
+        },
+    ));
 }
 
 fn button_actions(
     buttons: Query<&GameOverButton>,
     mut events: EventReader<NavEvent>,
     mut next_state: ResMut<NextState<GameState>>,
+    mut current: ResMut<CurrentLevel>,
 ) {
     for button in events.nav_iter().activated_in_query(&buttons) {
         match button {
+            GameOverButton::NextLevel => {
+                current.0 += 1;
+                next_state.set(GameState::Playing);
+            }
             GameOverButton::PlayAgain => {
                 next_state.set(GameState::MainMenu);
             }
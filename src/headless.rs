@@ -0,0 +1,331 @@
+use std::time::Duration;
+
+use bevy::{
+    app::ScheduleRunnerPlugin, hierarchy::HierarchyPlugin, prelude::*, scene::ScenePlugin,
+    state::app::StatesPlugin, time::TimeUpdateStrategy, transform::TransformPlugin,
+};
+
+use crate::{
+    currency::CurrencyPlugin,
+    damage::DamagePlugin,
+    enemy::EnemyPlugin,
+    level::Level,
+    lives::{Lives, LivesPlugin, Won},
+    map::{Floor, MapPlugin, TilePos},
+    rng::{GameRng, GameSeed},
+    score::score,
+    settings::DifficultySetting,
+    stats::{run_secs, RunStats, StatsPlugin},
+    status::StatusPlugin,
+    tower::{SpawnTowerEvent, TowerKind, TowerPlugin},
+    waves::{GameMode, WaveList, WavePlugin, Waves},
+    GameState, RunTick, SimulationPlugin, TickRate, TICK_RATE,
+};
+
+/// The level and wave files for each level, in the same order as `Levels`. These are
+/// embedded so that a simulation doesn't need to load any assets.
+const LEVELS: [(&str, &str); 3] = [
+    (
+        include_str!("../assets/levels/1.level.ron"),
+        include_str!("../assets/levels/1.waves.ron"),
+    ),
+    (
+        include_str!("../assets/levels/2.level.ron"),
+        include_str!("../assets/levels/2.waves.ron"),
+    ),
+    (
+        include_str!("../assets/levels/3.level.ron"),
+        include_str!("../assets/levels/3.waves.ron"),
+    ),
+];
+
+/// Simulated seconds after which a game that hasn't ended is abandoned.
+const TIME_LIMIT: f32 = 60. * 60.;
+
+const USAGE: &str = "usage: undefended --headless [--level N] [--difficulty Normal|Hard|Extra] \
+    [--mode Campaign|Endless] [--seed N] [--tick-rate N] [--tower X,Y,KIND]...";
+
+pub struct HeadlessOptions {
+    /// Index of the level to play in `LEVELS`.
+    pub level: usize,
+    pub difficulty: DifficultySetting,
+    pub mode: GameMode,
+    /// A random seed is picked if this is `None`.
+    pub seed: Option<u64>,
+    pub tick_rate: f64,
+    /// Towers to build when the game starts.
+    pub towers: Vec<(UVec2, TowerKind)>,
+}
+
+impl HeadlessOptions {
+    /// Reads the options from the command line, if the game was started with `--headless`.
+    ///
+    /// Exits the process if the options can't be parsed.
+    pub fn from_args() -> Option<Self> {
+        let mut args = std::env::args().skip(1);
+        if args.next().as_deref() != Some("--headless") {
+            return None;
+        }
+
+        match Self::parse(args) {
+            Ok(options) => Some(options),
+            Err(e) => {
+                eprintln!("{}\n{}", e, USAGE);
+                std::process::exit(2);
+            }
+        }
+    }
+
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut options = Self {
+            level: 0,
+            difficulty: DifficultySetting::Normal,
+            mode: GameMode::Campaign,
+            seed: None,
+            tick_rate: TICK_RATE,
+            towers: vec![],
+        };
+
+        while let Some(arg) = args.next() {
+            let value = args
+                .next()
+                .ok_or_else(|| format!("missing value for {}", arg))?;
+
+            match arg.as_str() {
+                "--level" => {
+                    let level: usize = value
+                        .parse()
+                        .map_err(|_| format!("invalid level '{}'", value))?;
+                    if level == 0 || level > LEVELS.len() {
+                        return Err(format!("level must be between 1 and {}", LEVELS.len()));
+                    }
+                    options.level = level - 1;
+                }
+                "--difficulty" => {
+                    options.difficulty = ron::de::from_str(&value)
+                        .map_err(|_| format!("invalid difficulty '{}'", value))?;
+                }
+                "--mode" => {
+                    options.mode = ron::de::from_str(&value)
+                        .map_err(|_| format!("invalid mode '{}'", value))?;
+                }
+                "--seed" => {
+                    let seed = value
+                        .parse()
+                        .map_err(|_| format!("invalid seed '{}'", value))?;
+                    options.seed = Some(seed);
+                }
+                "--tick-rate" => {
+                    let tick_rate: f64 = value
+                        .parse()
+                        .map_err(|_| format!("invalid tick rate '{}'", value))?;
+                    if !tick_rate.is_finite() || tick_rate <= 0. {
+                        return Err("tick rate must be greater than 0".to_string());
+                    }
+                    options.tick_rate = tick_rate;
+                }
+                "--tower" => {
+                    let parts: Vec<_> = value.split(',').collect();
+                    let [x, y, kind] = parts.as_slice() else {
+                        return Err(format!("invalid tower '{}'", value));
+                    };
+
+                    let (Ok(x), Ok(y)) = (x.parse(), y.parse()) else {
+                        return Err(format!("invalid tower position in '{}'", value));
+                    };
+                    let pos = UVec2::new(x, y);
+                    let kind = ron::de::from_str(kind)
+                        .map_err(|_| format!("invalid tower kind in '{}'", value))?;
+
+                    options.towers.push((pos, kind));
+                }
+                _ => return Err(format!("unknown option {}", arg)),
+            }
+        }
+
+        Ok(options)
+    }
+}
+
+/// The gameplay plugins, along with the bare minimum of Bevy needed to run them without
+/// a window, rendering, audio or asset files. Each frame advances time by exactly one
+/// tick.
+///
+/// The game starts in `GameState::Playing`, so the `Level` and `WaveList` to play must
+/// be inserted as resources before the first update.
+pub struct HeadlessPlugin {
+    pub tick_rate: f64,
+}
+impl Default for HeadlessPlugin {
+    fn default() -> Self {
+        Self {
+            tick_rate: TICK_RATE,
+        }
+    }
+}
+
+impl Plugin for HeadlessPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins((
+            MinimalPlugins.set(ScheduleRunnerPlugin::run_loop(Duration::ZERO)),
+            TransformPlugin,
+            HierarchyPlugin,
+            StatesPlugin,
+            AssetPlugin::default(),
+            ScenePlugin,
+        ))
+        // Physics needs somewhere to look for meshes, even though we don't have any.
+        .init_asset::<Mesh>()
+        .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f64(
+            1. / self.tick_rate,
+        )))
+        .add_plugins(SimulationPlugin {
+            tick_rate: self.tick_rate,
+        });
+
+        app.insert_state(GameState::Playing)
+            .init_resource::<DifficultySetting>()
+            .add_plugins(LivesPlugin)
+            .add_plugins(CurrencyPlugin)
+            .add_plugins(StatsPlugin)
+            .add_plugins(MapPlugin)
+            .add_plugins(EnemyPlugin)
+            .add_plugins(DamagePlugin)
+            .add_plugins(StatusPlugin)
+            .add_plugins(TowerPlugin)
+            .add_plugins(WavePlugin);
+    }
+}
+
+#[derive(Resource)]
+struct Towers(Vec<(UVec2, TowerKind)>);
+
+/// Plays a full game without a window, rendering, audio or asset files, as fast as
+/// possible, and prints the outcome.
+///
+/// Returns an error if the game was lost or didn't finish.
+pub fn run(options: HeadlessOptions) -> AppExit {
+    let (level, wave_list) = LEVELS[options.level];
+
+    let level: Level = match ron::de::from_str(level) {
+        Ok(level) => level,
+        Err(e) => {
+            eprintln!("Could not parse level: {}", e);
+            return AppExit::error();
+        }
+    };
+    if let Err(e) = level.validate() {
+        eprintln!("Invalid level: {}", e);
+        return AppExit::error();
+    }
+
+    let wave_list: WaveList = match ron::de::from_str(wave_list) {
+        Ok(wave_list) => wave_list,
+        Err(e) => {
+            eprintln!("Could not parse waves: {}", e);
+            return AppExit::error();
+        }
+    };
+    if let Err(e) = wave_list.validate() {
+        eprintln!("Invalid waves: {}", e);
+        return AppExit::error();
+    }
+
+    for (pos, _) in &options.towers {
+        if level.floor_tiles().all(|tile| tile != *pos) {
+            eprintln!("Can't build a tower at {}, which is not a floor tile.", pos);
+            return AppExit::error();
+        }
+    }
+
+    let mut app = App::new();
+
+    app.add_plugins(HeadlessPlugin {
+        tick_rate: options.tick_rate,
+    })
+    .insert_resource(level)
+    .insert_resource(wave_list)
+    .insert_resource(options.difficulty)
+    .insert_resource(options.mode)
+    .insert_resource(GameSeed(options.seed))
+    .insert_resource(Towers(options.towers))
+    .add_systems(Update, build_towers.run_if(in_state(GameState::Playing)))
+    .add_systems(Update, time_limit.run_if(in_state(GameState::Playing)))
+    .add_systems(OnEnter(GameState::GameOver), report);
+
+    app.run()
+}
+
+fn build_towers(
+    floor_query: Query<(Entity, &TilePos), Added<Floor>>,
+    towers: Res<Towers>,
+    mut events: EventWriter<SpawnTowerEvent>,
+) {
+    for (entity, tile_pos) in floor_query.iter() {
+        for (_, kind) in towers.0.iter().filter(|(pos, _)| *pos == tile_pos.0) {
+            events.send(SpawnTowerEvent(entity, *kind));
+        }
+    }
+}
+
+fn time_limit(
+    time: Res<Time>,
+    waves: Res<Waves>,
+    rng: Res<GameRng>,
+    mut exit: EventWriter<AppExit>,
+) {
+    if time.elapsed_secs() < TIME_LIMIT {
+        return;
+    }
+
+    println!(
+        "Gave up on {} after {:.1}s (seed {}).",
+        wave_progress(&waves),
+        time.elapsed_secs(),
+        rng.seed()
+    );
+
+    exit.send(AppExit::error());
+}
+
+fn wave_progress(waves: &Waves) -> String {
+    // Endless mode has no last wave
+    if waves.endless {
+        return format!("wave {}", waves.current + 1);
+    }
+
+    format!(
+        "wave {} of {}",
+        (waves.current + 1).min(waves.waves.len()),
+        waves.waves.len()
+    )
+}
+
+fn report(
+    won: Res<Won>,
+    lives: Res<Lives>,
+    waves: Res<Waves>,
+    time: Res<Time>,
+    rng: Res<GameRng>,
+    stats: Res<RunStats>,
+    tick: Res<RunTick>,
+    tick_rate: Res<TickRate>,
+    difficulty: Res<DifficultySetting>,
+    mut exit: EventWriter<AppExit>,
+) {
+    println!(
+        "{} on {} with {} lives left after {:.1}s, scoring {} (seed {}).",
+        if won.0 { "Won" } else { "Lost" },
+        wave_progress(&waves),
+        lives.0,
+        time.elapsed_secs(),
+        score(&stats, lives.0, run_secs(&tick, &tick_rate), &difficulty),
+        rng.seed()
+    );
+
+    exit.send(if won.0 {
+        AppExit::Success
+    } else {
+        AppExit::error()
+    });
+}
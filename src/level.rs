@@ -0,0 +1,304 @@
+use std::fmt::Display;
+
+use bevy::{
+    asset::{io::Reader, AssetLoader, LoadContext},
+    prelude::*,
+    utils::HashSet,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    lives::Won,
+    loading::Levels,
+    map::Item,
+    settings::DifficultySetting,
+    waves::{WaveList, Waves},
+    GameState,
+};
+
+pub struct LevelPlugin;
+
+/// A level, loaded from a `.level.ron` file.
+///
+/// The level being played is also inserted as a resource.
+#[derive(Asset, Resource, TypePath, Deserialize, Clone, Debug)]
+pub struct Level {
+    pub name: String,
+    /// Width and height of the grid, in tiles.
+    pub size: UVec2,
+    /// Rows of the grid, where `#` is a floor tile and `.` is empty space.
+    pub tiles: Vec<String>,
+    /// Waypoints that enemies walk between, starting at the enemy spawn and ending at
+    /// the goal. Each segment must be horizontal or vertical.
+    pub path: Vec<UVec2>,
+    pub player_start: UVec2,
+    #[serde(default)]
+    pub item_spawners: Vec<LevelItemSpawner>,
+    #[serde(default)]
+    pub moving_floors: Vec<LevelMovingFloor>,
+}
+
+/// Index of the level being played in `Levels::levels`.
+#[derive(Resource, Default)]
+pub struct CurrentLevel(pub usize);
+
+/// The number of levels that the player has unlocked, counting from the first.
+#[derive(Resource, Deref, DerefMut, Debug, Serialize, Deserialize, Clone)]
+pub struct UnlockedLevels(usize);
+impl Default for UnlockedLevels {
+    fn default() -> Self {
+        Self(1)
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct LevelItemSpawner {
+    pub pos: UVec2,
+    pub item: Item,
+    /// Seconds between an item being taken and the next one appearing.
+    pub interval: f32,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct LevelMovingFloor {
+    /// The floor starts at the first waypoint and travels back and forth along the
+    /// rest of them.
+    pub waypoints: Vec<UVec2>,
+    /// Speed in world units per second.
+    pub speed: f32,
+    /// Seconds to wait at either end.
+    pub dwell: f32,
+}
+
+impl Level {
+    /// Returns the positions of all the regular floor tiles.
+    pub fn floor_tiles(&self) -> impl Iterator<Item = UVec2> + '_ {
+        self.tiles.iter().enumerate().flat_map(|(row, row_val)| {
+            row_val
+                .chars()
+                .enumerate()
+                .filter(|(_, c)| *c == '#')
+                .map(move |(col, _)| UVec2::new(col as u32, row as u32))
+        })
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.tiles.len() != self.size.y as usize {
+            return Err(format!(
+                "expected {} rows of tiles, found {}",
+                self.size.y,
+                self.tiles.len()
+            ));
+        }
+
+        for (row, row_val) in self.tiles.iter().enumerate() {
+            if row_val.chars().count() != self.size.x as usize {
+                return Err(format!(
+                    "expected {} tiles in row {}, found {}",
+                    self.size.x,
+                    row,
+                    row_val.chars().count()
+                ));
+            }
+
+            if let Some(c) = row_val.chars().find(|c| *c != '#' && *c != '.') {
+                return Err(format!("unknown tile '{}' in row {}", c, row));
+            }
+        }
+
+        if self.path.len() < 2 {
+            return Err("path must have at least two waypoints".to_string());
+        }
+
+        for window in self.path.windows(2) {
+            let (start, end) = (window[0], window[1]);
+            if start.x != end.x && start.y != end.y {
+                return Err(format!(
+                    "path segment from {} to {} is not horizontal or vertical",
+                    start, end
+                ));
+            }
+        }
+
+        let positions = self
+            .path
+            .iter()
+            .chain(std::iter::once(&self.player_start))
+            .chain(self.item_spawners.iter().map(|spawner| &spawner.pos))
+            .chain(self.moving_floors.iter().flat_map(|floor| &floor.waypoints));
+        for pos in positions {
+            if pos.x >= self.size.x || pos.y >= self.size.y {
+                return Err(format!("position {} is outside of the grid", pos));
+            }
+        }
+
+        if self.floor_tiles().all(|pos| pos != self.player_start) {
+            return Err(format!(
+                "player start {} is not a floor tile",
+                self.player_start
+            ));
+        }
+
+        if self
+            .moving_floors
+            .iter()
+            .any(|floor| floor.waypoints.is_empty())
+        {
+            return Err("moving floors must have at least one waypoint".to_string());
+        }
+
+        let mut occupied: HashSet<UVec2> = self.floor_tiles().collect();
+        let positions = self
+            .item_spawners
+            .iter()
+            .map(|spawner| spawner.pos)
+            .chain(self.moving_floors.iter().map(|floor| floor.waypoints[0]));
+        for pos in positions {
+            if !occupied.insert(pos) {
+                return Err(format!("more than one tile at position {}", pos));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub enum LevelLoaderError {
+    Io(std::io::Error),
+    Ron(ron::error::SpannedError),
+    Invalid(String),
+}
+impl Display for LevelLoaderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "Could not read level: {}", e),
+            Self::Ron(e) => write!(f, "Could not parse level: {}", e),
+            Self::Invalid(e) => write!(f, "Invalid level: {}", e),
+        }
+    }
+}
+impl std::error::Error for LevelLoaderError {}
+
+#[derive(Default)]
+pub struct LevelLoader;
+impl AssetLoader for LevelLoader {
+    type Asset = Level;
+    type Settings = ();
+    type Error = LevelLoaderError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader
+            .read_to_end(&mut bytes)
+            .await
+            .map_err(LevelLoaderError::Io)?;
+
+        let level: Level = ron::de::from_bytes(&bytes).map_err(LevelLoaderError::Ron)?;
+        level.validate().map_err(LevelLoaderError::Invalid)?;
+
+        Ok(level)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["level.ron"]
+    }
+}
+
+impl Plugin for LevelPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<Level>()
+            .init_asset_loader::<LevelLoader>()
+            .init_resource::<CurrentLevel>()
+            .add_systems(OnExit(GameState::LevelSelect), insert_level)
+            .add_systems(OnExit(GameState::GameOver), insert_level)
+            .add_systems(OnEnter(GameState::GameOver), unlock_next_level)
+            .add_systems(Update, reload_waves.run_if(in_state(GameState::Playing)));
+    }
+}
+
+pub fn insert_level(
+    mut commands: Commands,
+    levels: Res<Levels>,
+    level_assets: Res<Assets<Level>>,
+    wave_lists: Res<Assets<WaveList>>,
+    current: Res<CurrentLevel>,
+) {
+    let Some(level) = levels
+        .levels
+        .get(current.0)
+        .and_then(|handle| level_assets.get(handle))
+    else {
+        error!("Level {} is missing.", current.0);
+        return;
+    };
+
+    let Some(wave_list) = levels
+        .waves
+        .get(current.0)
+        .and_then(|handle| wave_lists.get(handle))
+    else {
+        error!("Waves for level {} are missing.", current.0);
+        return;
+    };
+
+    commands.insert_resource(level.clone());
+    commands.insert_resource(wave_list.clone());
+}
+
+fn unlock_next_level(
+    won: Res<Won>,
+    current: Res<CurrentLevel>,
+    levels: Res<Levels>,
+    mut unlocked: ResMut<UnlockedLevels>,
+) {
+    if !won.0 {
+        return;
+    }
+
+    let next = (current.0 + 2).min(levels.levels.len());
+    if next > **unlocked {
+        **unlocked = next;
+    }
+}
+
+fn reload_waves(
+    mut commands: Commands,
+    mut events: EventReader<AssetEvent<WaveList>>,
+    levels: Res<Levels>,
+    current: Res<CurrentLevel>,
+    wave_lists: Res<Assets<WaveList>>,
+    difficulty: Res<DifficultySetting>,
+    mut waves: ResMut<Waves>,
+) {
+    let Some(handle) = levels.waves.get(current.0) else {
+        return;
+    };
+
+    if !events.read().any(|event| event.is_modified(handle)) {
+        return;
+    }
+
+    let Some(wave_list) = wave_lists.get(handle) else {
+        return;
+    };
+
+    info!("Reloaded waves.");
+
+    // Keep playing the current wave, but pick up any changes to it and to the
+    // waves that follow.
+    waves.waves = wave_list.waves(&difficulty);
+    if waves.endless {
+        // The waves after the level's own are generated again from the new ones.
+        waves.generate();
+    } else {
+        waves.current = waves.current.min(waves.waves.len());
+    }
+
+    commands.insert_resource(wave_list.clone());
+}
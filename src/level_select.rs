@@ -0,0 +1,187 @@
+use bevy::prelude::*;
+use bevy_alt_ui_navigation_lite::prelude::*;
+
+use crate::{
+    level::{CurrentLevel, Level, UnlockedLevels},
+    loading::{Fonts, Levels},
+    ui::{
+        buttons, BUTTON_TEXT, CONTAINER_BACKGROUND, LOCKED_BUTTON_TEXT, NORMAL_BUTTON, TITLE_TEXT,
+    },
+    GameState,
+};
+
+pub struct LevelSelectPlugin;
+impl Plugin for LevelSelectPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(GameState::LevelSelect), setup_menu)
+            .add_systems(
+                Update,
+                (button_actions, buttons.after(NavRequestSystem))
+                    .run_if(in_state(GameState::LevelSelect)),
+            )
+            .add_systems(OnExit(GameState::LevelSelect), cleanup_menu);
+    }
+}
+
+#[derive(Component)]
+struct LevelSelectMarker;
+
+#[derive(Component, Debug)]
+enum LevelSelectButton {
+    Level(usize),
+    Back,
+}
+
+fn setup_menu(
+    mut commands: Commands,
+    fonts: Res<Fonts>,
+    levels: Res<Levels>,
+    level_assets: Res<Assets<Level>>,
+    unlocked: Res<UnlockedLevels>,
+    current: Res<CurrentLevel>,
+) {
+    let button_style = Node {
+        width: Val::Px(250.0),
+        height: Val::Px(45.0),
+        margin: UiRect::all(Val::Px(5.0)),
+        justify_content: JustifyContent::Center,
+        align_items: AlignItems::Center,
+        ..default()
+    };
+    let button_text_style = (
+        TextFont {
+            font: fonts.main.clone(),
+            font_size: 25.0,
+            ..default()
+        },
+        TextColor(BUTTON_TEXT.into()),
+    );
+    let title_text_style = (
+        TextFont {
+            font: fonts.main.clone(),
+            font_size: 50.0,
+            ..default()
+        },
+        TextColor(TITLE_TEXT.into()),
+    );
+
+    let container = commands
+        .spawn((
+            Node {
+                margin: UiRect::all(Val::Auto),
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                padding: UiRect::all(Val::Px(20.)),
+                ..default()
+            },
+            BackgroundColor(CONTAINER_BACKGROUND.into()),
+            LevelSelectMarker,
+        ))
+        .id();
+
+    let title = commands
+        .spawn((
+            Text::new("- LEVELS -"),
+            title_text_style,
+            Node {
+                margin: UiRect {
+                    bottom: Val::Px(10.0),
+                    ..default()
+                },
+                ..default()
+            },
+        ))
+        .id();
+
+    commands.entity(container).add_child(title);
+
+    for (i, handle) in levels.levels.iter().enumerate() {
+        let name = level_assets
+            .get(handle)
+            .map(|level| level.name.to_uppercase())
+            .unwrap_or_default();
+
+        let level_button = if i < **unlocked {
+            let focusable = if i == current.0.min(**unlocked - 1) {
+                Focusable::default().prioritized()
+            } else {
+                Focusable::default()
+            };
+
+            commands
+                .spawn((
+                    Button,
+                    button_style.clone(),
+                    BackgroundColor(NORMAL_BUTTON.into()),
+                    focusable,
+                    LevelSelectButton::Level(i),
+                ))
+                .with_children(|parent| {
+                    parent.spawn((
+                        Text::new(format!("{}. {}", i + 1, name)),
+                        button_text_style.clone(),
+                    ));
+                })
+                .id()
+        } else {
+            commands
+                .spawn((button_style.clone(), BackgroundColor(NORMAL_BUTTON.into())))
+                .with_children(|parent| {
+                    parent.spawn((
+                        Text::new(format!("{}. LOCKED", i + 1)),
+                        button_text_style.0.clone(),
+                        TextColor(LOCKED_BUTTON_TEXT.into()),
+                    ));
+                })
+                .id()
+        };
+
+        commands.entity(container).add_child(level_button);
+    }
+
+    let back_button = commands
+        .spawn((
+            Button,
+            Node {
+                margin: UiRect {
+                    top: Val::Px(20.0),
+                    ..button_style.margin
+                },
+                ..button_style
+            },
+            BackgroundColor(NORMAL_BUTTON.into()),
+            Focusable::default(),
+            LevelSelectButton::Back,
+        ))
+        .with_children(|parent| {
+            parent.spawn((Text::new("BACK"), button_text_style));
+        })
+        .id();
+
+    commands.entity(container).add_child(back_button);
+}
+
+fn button_actions(
+    buttons: Query<&LevelSelectButton>,
+    mut events: EventReader<NavEvent>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut current: ResMut<CurrentLevel>,
+) {
+    for button in events.nav_iter().activated_in_query(&buttons) {
+        match button {
+            LevelSelectButton::Level(i) => {
+                current.0 = *i;
+                next_state.set(GameState::Playing);
+            }
+            LevelSelectButton::Back => {
+                next_state.set(GameState::MainMenu);
+            }
+        }
+    }
+}
+
+fn cleanup_menu(mut commands: Commands, query: Query<Entity, With<LevelSelectMarker>>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
@@ -0,0 +1,249 @@
+#![allow(clippy::too_many_arguments, clippy::type_complexity)]
+
+use bevy::{
+    audio::Volume, ecs::schedule::ExecutorKind, pbr::CascadeShadowConfigBuilder, prelude::*,
+    transform::TransformSystem,
+};
+use bevy_alt_ui_navigation_lite::{systems::InputMapping, DefaultNavigationPlugins};
+use bevy_rapier3d::prelude::*;
+use bevy_scene_hook::HookPlugin;
+
+use camera::CameraPlugin;
+use currency::CurrencyPlugin;
+use damage::DamagePlugin;
+use enemy::{EnemyPlugin, EnemyPresentationPlugin};
+use game_over::GameOverPlugin;
+use level::LevelPlugin;
+use level_select::LevelSelectPlugin;
+use lives::LivesPlugin;
+use loading::{LoadingPlugin, Sounds};
+use main_menu::MainMenuPlugin;
+use map::{MapPlugin, MapPresentationPlugin};
+use outline::OutlinePlugin;
+use pause::PausePlugin;
+use player::PlayerPlugin;
+use replay::ReplayPlugin;
+use rng::RngPlugin;
+use save::SavePlugin;
+use score::ScorePlugin;
+use settings::MusicSetting;
+use starfield::StarfieldPlugin;
+use stats::StatsPlugin;
+use status::{StatusPlugin, StatusPresentationPlugin};
+use tower::{TowerPlugin, TowerPresentationPlugin};
+use ui::UiPlugin;
+use waves::WavePlugin;
+
+pub mod camera;
+pub mod currency;
+pub mod damage;
+pub mod enemy;
+pub mod game_over;
+pub mod headless;
+pub mod level;
+pub mod level_select;
+pub mod lives;
+pub mod loading;
+pub mod main_menu;
+pub mod map;
+pub mod outline;
+pub mod pause;
+pub mod player;
+pub mod replay;
+pub mod rng;
+pub mod save;
+pub mod score;
+pub mod settings;
+pub mod starfield;
+pub mod stats;
+pub mod status;
+pub mod tower;
+pub mod ui;
+pub mod waves;
+
+/// Gameplay ticks per second, unless configured otherwise.
+pub const TICK_RATE: f64 = 60.;
+
+/// The whole game, minus the window and asset configuration that `DefaultPlugins`
+/// provides.
+pub struct GamePlugin {
+    pub tick_rate: f64,
+}
+impl Default for GamePlugin {
+    fn default() -> Self {
+        Self {
+            tick_rate: TICK_RATE,
+        }
+    }
+}
+
+/// Runs physics in `FixedUpdate` at `tick_rate` ticks per second, and seeds a `GameRng`
+/// for each run.
+///
+/// Gameplay systems also run in `FixedUpdate`, so that a run only depends on its seed and
+/// inputs, and not on the frame rate.
+pub struct SimulationPlugin {
+    pub tick_rate: f64,
+}
+
+/// Ticks per second.
+#[derive(Resource, Deref)]
+pub struct TickRate(pub f64);
+
+/// The number of ticks since the current run started.
+#[derive(Resource, Default, Deref)]
+pub struct RunTick(pub u32);
+
+impl Plugin for SimulationPlugin {
+    fn build(&self, app: &mut App) {
+        // The multi-threaded executor doesn't run conflicting systems that aren't ordered
+        // relative to each other in a consistent order.
+        app.edit_schedule(FixedUpdate, |schedule| {
+            schedule.set_executor_kind(ExecutorKind::SingleThreaded);
+        });
+
+        app.init_resource::<RunTick>()
+            .add_systems(OnEnter(GameState::Playing), reset_run_tick)
+            .add_systems(
+                FixedPostUpdate,
+                advance_run_tick.run_if(in_state(GameState::Playing)),
+            );
+
+        app.insert_resource(TickRate(self.tick_rate))
+            .insert_resource(Time::<Fixed>::from_hz(self.tick_rate))
+            .add_plugins(RapierPhysicsPlugin::<NoUserData>::default().in_fixed_schedule())
+            .insert_resource(TimestepMode::Fixed {
+                dt: (1. / self.tick_rate) as f32,
+                substeps: 1,
+            })
+            .add_plugins(RngPlugin);
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash, States)]
+pub enum GameState {
+    #[default]
+    Loading,
+    Pipelines,
+    MainMenu,
+    LevelSelect,
+    Playing,
+    /// Passed through when a run is restarted, so that it's torn down and set up again.
+    Restarting,
+    GameOver,
+}
+
+#[derive(Debug, Hash, PartialEq, Eq, Clone, SystemSet)]
+pub struct AfterPhysics;
+
+#[derive(Component)]
+pub struct MusicController;
+
+#[derive(Component)]
+pub struct DespawnOnReset;
+
+impl Plugin for GamePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_state::<GameState>();
+
+        // Physics runs in `FixedUpdate`, so it has always been written back by now.
+        // TODO we may need apply_deferred somewhere in here
+        app.configure_sets(
+            PostUpdate,
+            AfterPhysics.before(TransformSystem::TransformPropagate),
+        );
+
+        app.add_systems(OnEnter(GameState::Playing), setup)
+            .add_systems(OnExit(GameState::Pipelines), start_music)
+            .add_systems(OnExit(GameState::GameOver), reset)
+            // A run that's abandoned doesn't go through `GameOver`
+            .add_systems(
+                OnExit(GameState::Playing),
+                reset.run_if(not(in_state(GameState::GameOver))),
+            );
+
+        app.add_plugins(SimulationPlugin {
+            tick_rate: self.tick_rate,
+        });
+
+        app.insert_resource(InputMapping {
+            keyboard_navigation: true,
+            ..default()
+        })
+        .add_plugins(DefaultNavigationPlugins)
+        .add_plugins(HookPlugin);
+
+        app.add_plugins(LivesPlugin)
+            .add_plugins(CurrencyPlugin)
+            .add_plugins(StatsPlugin)
+            .add_plugins(ScorePlugin)
+            .add_plugins(LevelPlugin)
+            .add_plugins(LoadingPlugin)
+            .add_plugins(StarfieldPlugin)
+            .add_plugins(CameraPlugin)
+            .add_plugins(PlayerPlugin)
+            .add_plugins(ReplayPlugin)
+            .add_plugins(MapPlugin)
+            .add_plugins(MapPresentationPlugin)
+            .add_plugins(EnemyPlugin)
+            .add_plugins(DamagePlugin)
+            .add_plugins(EnemyPresentationPlugin)
+            .add_plugins(StatusPlugin)
+            .add_plugins(StatusPresentationPlugin)
+            .add_plugins(TowerPlugin)
+            .add_plugins(TowerPresentationPlugin)
+            .add_plugins(MainMenuPlugin)
+            .add_plugins(LevelSelectPlugin)
+            .add_plugins(SavePlugin)
+            .add_plugins(UiPlugin)
+            .add_plugins(WavePlugin)
+            .add_plugins(GameOverPlugin)
+            .add_plugins(OutlinePlugin)
+            .add_plugins(PausePlugin);
+    }
+}
+
+fn reset_run_tick(mut tick: ResMut<RunTick>) {
+    tick.0 = 0;
+}
+
+fn advance_run_tick(mut tick: ResMut<RunTick>) {
+    tick.0 += 1;
+}
+
+fn setup(mut commands: Commands) {
+    // light
+    commands.spawn((
+        DirectionalLight {
+            illuminance: 2500.0,
+            shadows_enabled: true,
+            ..default()
+        },
+        Transform::from_rotation(Quat::from_euler(EulerRot::YXZ, -1.0, -1.0, -1.0)),
+        CascadeShadowConfigBuilder {
+            first_cascade_far_bound: 4.0,
+            maximum_distance: 30.0,
+            ..default()
+        }
+        .build(),
+        DespawnOnReset,
+    ));
+}
+
+fn start_music(
+    mut commands: Commands,
+    music_setting: Res<MusicSetting>,
+    audio_assets: Res<Sounds>,
+) {
+    commands.spawn((
+        AudioPlayer(audio_assets.music.clone()),
+        PlaybackSettings::LOOP.with_volume(Volume::new(**music_setting as f32 / 100.)),
+        MusicController,
+    ));
+}
+
+fn reset(mut commands: Commands, to_despawn: Query<Entity, With<DespawnOnReset>>) {
+    for entity in to_despawn.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
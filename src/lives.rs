@@ -0,0 +1,58 @@
+use bevy::prelude::*;
+
+use crate::{
+    enemy::Enemy,
+    waves::{WaveState, Waves},
+    GameState,
+};
+
+pub struct LivesPlugin;
+
+#[derive(Resource, Reflect)]
+#[reflect(Resource)]
+pub struct Lives(pub u32);
+impl Default for Lives {
+    fn default() -> Self {
+        Self(3)
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct Won(pub bool);
+
+impl Plugin for LivesPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Lives>()
+            .register_type::<Lives>()
+            .init_resource::<Won>()
+            .add_systems(FixedUpdate, game_over.run_if(in_state(GameState::Playing)))
+            .add_systems(OnExit(GameState::GameOver), reset)
+            .add_systems(
+                OnExit(GameState::Playing),
+                reset.run_if(not(in_state(GameState::GameOver))),
+            );
+    }
+}
+
+fn game_over(
+    mut commands: Commands,
+    lives: Res<Lives>,
+    waves: Res<Waves>,
+    wave_state: Res<WaveState>,
+    enemies: Query<(), With<Enemy>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if waves.current().is_none() && wave_state.remaining == 0 && enemies.iter().len() == 0 {
+        commands.insert_resource(Won(true));
+        next_state.set(GameState::GameOver);
+    }
+
+    if lives.0 == 0 {
+        commands.insert_resource(Won(false));
+        next_state.set(GameState::GameOver);
+    }
+}
+
+fn reset(mut commands: Commands) {
+    commands.insert_resource(Lives::default());
+}
@@ -2,14 +2,44 @@ use bevy::{pbr::CascadeShadowConfigBuilder, prelude::*};
 use bevy_asset_loader::prelude::*;
 use bevy_mod_outline::OutlineVolume;
 use bevy_pipelines_ready::{PipelinesReady, PipelinesReadyPlugin};
+use iyes_progress::{ProgressPlugin, ProgressTracker};
 
-use crate::{map::PathMaterial, tower::LaserMaterial, GameState};
+use crate::{
+    level::Level,
+    map::PathMaterial,
+    tower::LaserMaterial,
+    ui::{ALT_TEXT, CONTAINER_BACKGROUND, UI_TEXT},
+    waves::WaveList,
+    GameState,
+};
 
 pub struct LoadingPlugin;
 
 #[derive(Component)]
 pub struct PipelinesMarker;
 
+/// The loading screen, shown until the main menu is reached.
+#[derive(Component)]
+struct LoadingScreenMarker;
+
+/// A camera to show the loading screen with, until the main camera is spawned.
+#[derive(Component)]
+struct LoadingCamera;
+
+#[derive(Component)]
+struct LoadingText;
+
+#[derive(Component)]
+struct LoadingBar;
+
+/// Seconds to wait for another pipeline to become ready before giving up and continuing
+/// to the main menu anyway, in case `EXPECTED_PIPELINES` is never reached.
+const PIPELINES_TIMEOUT: f32 = 5.;
+
+/// Counts down from when another pipeline last became ready.
+#[derive(Resource)]
+struct PipelinesTimeout(Timer);
+
 #[derive(AssetCollection, Resource)]
 pub struct Models {
     #[asset(path = "models/tile1.glb#Scene0")]
@@ -34,6 +64,21 @@ pub struct Models {
     pub player: Handle<Scene>,
 }
 
+#[derive(AssetCollection, Resource)]
+pub struct Levels {
+    #[asset(
+        paths("levels/1.level.ron", "levels/2.level.ron", "levels/3.level.ron"),
+        collection(typed)
+    )]
+    pub levels: Vec<Handle<Level>>,
+    /// The waves for each of `levels`, in the same order.
+    #[asset(
+        paths("levels/1.waves.ron", "levels/2.waves.ron", "levels/3.waves.ron"),
+        collection(typed)
+    )]
+    pub waves: Vec<Handle<WaveList>>,
+}
+
 #[derive(AssetCollection, Resource)]
 pub struct Fonts {
     #[asset(path = "fonts/Orbitron-Medium.ttf")]
@@ -72,18 +117,28 @@ const EXPECTED_PIPELINES: usize = 26;
 impl Plugin for LoadingPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugins(PipelinesReadyPlugin)
+            .add_plugins(
+                ProgressPlugin::<GameState>::new()
+                    .with_state_transition(GameState::Loading, GameState::Pipelines),
+            )
             .add_loading_state(
                 LoadingState::new(GameState::Loading)
                     .load_collection::<Models>()
+                    .load_collection::<Levels>()
                     .load_collection::<Fonts>()
                     .load_collection::<Images>()
-                    .load_collection::<Sounds>()
-                    .continue_to_state(GameState::Pipelines),
+                    .load_collection::<Sounds>(),
             )
+            .add_systems(OnEnter(GameState::Loading), setup_loading_screen)
+            .add_systems(OnExit(GameState::Loading), cleanup_loading_camera)
             .add_systems(
                 Update,
                 (
+                    loading_progress
+                        .run_if(in_state(GameState::Loading))
+                        .after(LoadingStateSet(GameState::Loading)),
                     pipelines_print.run_if(resource_changed::<PipelinesReady>),
+                    pipelines_progress.run_if(in_state(GameState::Pipelines)),
                     pipelines_done.run_if(in_state(GameState::Pipelines)),
                 ),
             )
@@ -99,7 +154,10 @@ fn setup_pipelines(
     laser_material: Res<LaserMaterial>,
     path_material: Res<PathMaterial>,
 ) {
-    commands.spawn((PipelinesMarker, Text::new("Loading Pipelines...")));
+    commands.insert_resource(PipelinesTimeout(Timer::from_seconds(
+        PIPELINES_TIMEOUT,
+        TimerMode::Once,
+    )));
 
     // Spawn enough things to trigger the creation of all the pipelines required for the
     // game.
@@ -145,14 +203,147 @@ fn pipelines_print(ready: Res<PipelinesReady>) {
     info!("Pipelines Ready: {}/{}", ready.get(), EXPECTED_PIPELINES);
 }
 
-fn pipelines_done(ready: Res<PipelinesReady>, mut next_state: ResMut<NextState<GameState>>) {
+fn pipelines_progress(
+    ready: Res<PipelinesReady>,
+    mut text_query: Query<&mut Text, With<LoadingText>>,
+    mut bar_query: Query<&mut Node, With<LoadingBar>>,
+) {
+    set_progress(
+        "PREPARING SHADERS",
+        ready.get().min(EXPECTED_PIPELINES) as u32,
+        EXPECTED_PIPELINES as u32,
+        &mut text_query,
+        &mut bar_query,
+    );
+}
+
+fn pipelines_done(
+    ready: Res<PipelinesReady>,
+    time: Res<Time>,
+    mut timeout: ResMut<PipelinesTimeout>,
+    mut last_ready: Local<usize>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
     if ready.get() >= EXPECTED_PIPELINES {
         next_state.set(GameState::MainMenu);
+        return;
+    }
+
+    if ready.get() != *last_ready {
+        *last_ready = ready.get();
+        timeout.0.reset();
+    }
+
+    if timeout.0.tick(time.delta()).just_finished() {
+        warn!(
+            "Only {}/{} pipelines became ready. Continuing anyway.",
+            ready.get(),
+            EXPECTED_PIPELINES
+        );
+        next_state.set(GameState::MainMenu);
+    }
+}
+
+fn setup_loading_screen(mut commands: Commands) {
+    commands.spawn((LoadingCamera, Camera2d));
+
+    // The game's fonts are still loading, so this uses Bevy's default font.
+    commands
+        .spawn((
+            LoadingScreenMarker,
+            Name::new("LoadingScreen"),
+            Node {
+                width: Val::Percent(100.),
+                height: Val::Percent(100.),
+                flex_direction: FlexDirection::Column,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                row_gap: Val::Px(10.),
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                LoadingText,
+                Text::new("LOADING"),
+                TextFont {
+                    font_size: 20.0,
+                    ..default()
+                },
+                TextColor(ALT_TEXT.into()),
+            ));
+            parent
+                .spawn((
+                    Node {
+                        width: Val::Px(300.),
+                        height: Val::Px(20.),
+                        padding: UiRect::all(Val::Px(3.)),
+                        ..default()
+                    },
+                    BackgroundColor(CONTAINER_BACKGROUND.into()),
+                ))
+                .with_child((
+                    LoadingBar,
+                    Node {
+                        width: Val::Percent(0.),
+                        height: Val::Percent(100.),
+                        ..default()
+                    },
+                    BackgroundColor(UI_TEXT.into()),
+                ));
+        });
+}
+
+fn loading_progress(
+    progress: Res<ProgressTracker<GameState>>,
+    mut text_query: Query<&mut Text, With<LoadingText>>,
+    mut bar_query: Query<&mut Node, With<LoadingBar>>,
+) {
+    let progress = progress.get_global_progress();
+
+    set_progress(
+        "LOADING ASSETS",
+        progress.done,
+        progress.total,
+        &mut text_query,
+        &mut bar_query,
+    );
+}
+
+fn set_progress(
+    label: &str,
+    done: u32,
+    total: u32,
+    text_query: &mut Query<&mut Text, With<LoadingText>>,
+    bar_query: &mut Query<&mut Node, With<LoadingBar>>,
+) {
+    for mut text in text_query.iter_mut() {
+        text.0 = format!("{} {}/{}", label, done, total);
+    }
+
+    let fraction = if total == 0 {
+        0.
+    } else {
+        done as f32 / total as f32
+    };
+    for mut node in bar_query.iter_mut() {
+        node.width = Val::Percent(fraction * 100.);
     }
 }
 
-fn cleanup(mut commands: Commands, query: Query<Entity, With<PipelinesMarker>>) {
+fn cleanup_loading_camera(mut commands: Commands, query: Query<Entity, With<LoadingCamera>>) {
     for entity in query.iter() {
         commands.entity(entity).despawn_recursive();
     }
 }
+
+fn cleanup(
+    mut commands: Commands,
+    query: Query<Entity, Or<(With<PipelinesMarker>, With<LoadingScreenMarker>)>>,
+) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    commands.remove_resource::<PipelinesTimeout>();
+}
@@ -6,10 +6,12 @@ use bevy_alt_ui_navigation_lite::prelude::*;
 
 use crate::{
     loading::{Fonts, Sounds},
-    settings::{DifficultySetting, MusicSetting, SfxSetting},
-    ui::{
-        buttons, ALT_TEXT, BUTTON_TEXT, CONTAINER_BACKGROUND, NORMAL_BUTTON, TITLE_TEXT, UI_TEXT,
+    settings::{
+        cycle_volume, DifficultySetting, HealthBarSetting, HealthBarSettingButtonText,
+        MusicSetting, MusicSettingButtonText, SfxSetting, SfxSettingButtonText,
     },
+    ui::{buttons, controls, BUTTON_TEXT, CONTAINER_BACKGROUND, NORMAL_BUTTON, TITLE_TEXT},
+    waves::GameMode,
     GameState, MusicController,
 };
 
@@ -19,14 +21,14 @@ impl Plugin for MainMenuPlugin {
         app.add_systems(OnEnter(GameState::MainMenu), setup_menu)
             .add_systems(
                 Update,
-                (
-                    sfx_volume,
-                    music_volume,
-                    button_actions,
-                    buttons.after(NavRequestSystem),
-                )
+                (button_actions, buttons.after(NavRequestSystem))
                     .run_if(in_state(GameState::MainMenu)),
             )
+            // Settings can also be changed from the pause menu
+            .add_systems(
+                Update,
+                (sfx_volume.run_if(resource_exists::<Sounds>), music_volume),
+            )
             .add_systems(OnExit(GameState::MainMenu), cleanup_menu);
     }
 }
@@ -39,17 +41,14 @@ struct PlayButton;
 #[derive(Component)]
 struct MusicSettingButton;
 #[derive(Component)]
-struct MusicSettingButtonText;
-#[derive(Component)]
 struct SfxSettingButton;
-
-#[derive(Component)]
-struct SfxSettingButtonText;
 #[derive(Component)]
 struct DifficultySettingButton;
 
 #[derive(Component)]
 struct DifficultySettingButtonText;
+#[derive(Component)]
+struct HealthBarSettingButton;
 
 fn setup_menu(
     mut commands: Commands,
@@ -57,6 +56,7 @@ fn setup_menu(
     sfx: Res<SfxSetting>,
     music: Res<MusicSetting>,
     difficulty: Res<DifficultySetting>,
+    health_bars: Res<HealthBarSetting>,
 ) {
     let button_style = Node {
         width: Val::Px(250.0),
@@ -133,6 +133,19 @@ fn setup_menu(
         })
         .id();
 
+    let endless_button = commands
+        .spawn((
+            Button,
+            button_style.clone(),
+            BackgroundColor(NORMAL_BUTTON.into()),
+            Focusable::default(),
+            MenuButton::Endless,
+        ))
+        .with_children(|parent| {
+            parent.spawn((Text::new("ENDLESS"), button_text_style.clone()));
+        })
+        .id();
+
     let audio_settings_title = commands
         .spawn((
             Text::new("- AUDIO -"),
@@ -144,6 +157,17 @@ fn setup_menu(
         ))
         .id();
 
+    let display_settings_title = commands
+        .spawn((
+            Text::new("- DISPLAY -"),
+            subtitle_text_style.clone(),
+            Node {
+                margin: UiRect::all(Val::Px(10.0)),
+                ..default()
+            },
+        ))
+        .id();
+
     let difficulty_title = commands
         .spawn((
             Text::new("- DIFFICULTY -"),
@@ -184,13 +208,31 @@ fn setup_menu(
         ))
         .with_children(|parent| {
             parent.spawn((
-                Text::new(format!("SFX {}%", **sfx)),
+                Text::new(sfx.label()),
                 button_text_style.clone(),
                 SfxSettingButtonText,
             ));
         })
         .id();
 
+    let health_bars_button = commands
+        .spawn((
+            Button,
+            button_style.clone(),
+            BackgroundColor(NORMAL_BUTTON.into()),
+            Focusable::default(),
+            MenuButton::HealthBars,
+            HealthBarSettingButton,
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                Text::new(health_bars.label()),
+                button_text_style.clone(),
+                HealthBarSettingButtonText,
+            ));
+        })
+        .id();
+
     let music_button = commands
         .spawn((
             Button,
@@ -202,7 +244,7 @@ fn setup_menu(
         ))
         .with_children(|parent| {
             parent.spawn((
-                Text::new(format!("MUSIC {}%", **music)),
+                Text::new(music.label()),
                 button_text_style,
                 MusicSettingButtonText,
             ));
@@ -212,11 +254,14 @@ fn setup_menu(
     commands.entity(container).add_children(&[
         title,
         play_button,
+        endless_button,
         difficulty_title,
         difficulty_button,
         audio_settings_title,
         sfx_button,
         music_button,
+        display_settings_title,
+        health_bars_button,
     ]);
 
     commands
@@ -236,48 +281,17 @@ fn setup_menu(
             },
             MainMenuMarker,
         ))
-        .with_children(|parent| {
-            parent.spawn((
-                Text::new("MOVE\nJUMP\nINTERACT"),
-                TextFont {
-                    font: fonts.main.clone(),
-                    font_size: 20.0,
-                    ..default()
-                },
-                TextColor(UI_TEXT.into()),
-                TextLayout::new_with_justify(JustifyText::Right),
-            ));
-            parent.spawn((
-                Text::new("\u{21CB}\u{21CE}\n\u{21A7}\n\u{21A4}"),
-                TextFont {
-                    font: fonts.prompts.clone(),
-                    font_size: 20.0,
-                    ..default()
-                },
-                TextColor(ALT_TEXT.into()),
-            ));
-            parent.spawn((
-                Text::new(concat!(
-                    "\u{FF37}\u{FF21}\u{FF33}\u{FF24}\u{23F6}\u{23F4}\u{23F5}\u{23F7}\n",
-                    "SPACE\n",
-                    "\u{FF32}"
-                )),
-                TextFont {
-                    font: fonts.prompts.clone(),
-                    font_size: 20.0,
-                    ..default()
-                },
-                TextColor(ALT_TEXT.into()),
-            ));
-        });
+        .with_children(|parent| controls(parent, &fonts));
 }
 
 #[derive(Component, Debug)]
 enum MenuButton {
     Play,
+    Endless,
     Sfx,
     Music,
     Difficulty,
+    HealthBars,
 }
 
 fn button_actions(
@@ -289,35 +303,35 @@ fn button_actions(
         Query<&mut Text, With<SfxSettingButtonText>>,
         Query<&mut Text, With<MusicSettingButtonText>>,
         Query<&mut Text, With<DifficultySettingButtonText>>,
+        Query<&mut Text, With<HealthBarSettingButtonText>>,
     )>,
     mut sfx_setting: ResMut<SfxSetting>,
     mut difficulty_setting: ResMut<DifficultySetting>,
+    mut health_bar_setting: ResMut<HealthBarSetting>,
+    mut mode: ResMut<GameMode>,
 ) {
     for button in events.nav_iter().activated_in_query(&buttons) {
         match button {
             MenuButton::Play => {
-                next_state.set(GameState::Playing);
+                *mode = GameMode::Campaign;
+                next_state.set(GameState::LevelSelect);
+            }
+            MenuButton::Endless => {
+                *mode = GameMode::Endless;
+                next_state.set(GameState::LevelSelect);
             }
             MenuButton::Sfx => {
-                if **sfx_setting == 0 {
-                    **sfx_setting = 100;
-                } else {
-                    **sfx_setting -= 10;
-                }
+                **sfx_setting = cycle_volume(**sfx_setting);
 
                 for mut text in text_queries.p0().iter_mut() {
-                    text.0 = format!("SFX {}%", **sfx_setting);
+                    text.0 = sfx_setting.label();
                 }
             }
             MenuButton::Music => {
-                if **music_setting == 0 {
-                    **music_setting = 100;
-                } else {
-                    **music_setting -= 10;
-                }
+                **music_setting = cycle_volume(**music_setting);
 
                 for mut text in text_queries.p1().iter_mut() {
-                    text.0 = format!("MUSIC {}%", **music_setting);
+                    text.0 = music_setting.label();
                 }
             }
             MenuButton::Difficulty => {
@@ -327,6 +341,13 @@ fn button_actions(
                     text.0 = format!("{}", *difficulty_setting);
                 }
             }
+            MenuButton::HealthBars => {
+                *health_bar_setting = health_bar_setting.next();
+
+                for mut text in text_queries.p3().iter_mut() {
+                    text.0 = health_bar_setting.label();
+                }
+            }
         }
     }
 }
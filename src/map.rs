@@ -4,44 +4,21 @@ use bevy::{prelude::*, utils::HashSet};
 use bevy_mod_outline::{AsyncSceneInheritOutline, OutlineVolume};
 use bevy_rapier3d::prelude::*;
 use bevy_tnua::TnuaPipelineStages;
-use rand::{seq::SliceRandom, thread_rng, Rng};
+use rand::Rng;
+use serde::Deserialize;
 
-use crate::{loading::Models, DespawnOnReset, GameState};
+use crate::{
+    level::Level,
+    loading::Models,
+    rng::{self, GameRng},
+    tower::TowerKind,
+    DespawnOnReset, GameState, RunTick,
+};
 
 pub struct MapPlugin;
 
-const MAP: [[i32; 15]; 15] = [
-    [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
-    [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
-    [0, 0, 0, 0, 0, 1, 1, 1, 1, 1, 1, 0, 0, 0, 0],
-    [0, 0, 0, 0, 0, 1, 0, 1, 0, 1, 1, 0, 0, 0, 0],
-    [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
-    [0, 0, 0, 0, 0, 1, 0, 0, 0, 1, 1, 0, 1, 0, 0],
-    [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0],
-    [0, 0, 0, 0, 1, 1, 0, 0, 0, 0, 0, 0, 1, 0, 0],
-    [0, 0, 0, 0, 1, 1, 0, 0, 0, 0, 0, 0, 1, 0, 0],
-    [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
-    [0, 0, 0, 1, 1, 1, 0, 0, 0, 0, 0, 0, 1, 0, 0],
-    [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 3, 0],
-    [0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 2, 1, 1, 4, 0],
-    [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
-    [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
-];
-const MAP_ROWS: usize = MAP.len();
-const MAP_COLS: usize = MAP[0].len();
 const TILE_SIZE: Vec3 = Vec3::new(2., 0.5, 2.);
 const LAVA_DEPTH: f32 = -20.;
-pub const PATH: [UVec2; 8] = [
-    UVec2::new(0, 4),
-    UVec2::new(11, 4),
-    UVec2::new(11, 10),
-    UVec2::new(6, 10),
-    UVec2::new(6, 9),
-    UVec2::new(3, 9),
-    UVec2::new(3, 7),
-    UVec2::new(0, 7),
-];
-pub const START_TILE: UVec2 = UVec2::new(12, 11);
 
 #[derive(Component)]
 pub struct Floor;
@@ -49,6 +26,14 @@ pub struct Floor;
 #[derive(Component)]
 pub struct TilePos(pub UVec2);
 
+/// Which of the floor tile models a tile uses. This is picked along with the rest of
+/// the map so that the model doesn't affect the run's random numbers.
+#[derive(Component)]
+struct FloorVariant(usize);
+
+/// The number of floor tile models.
+const FLOOR_VARIANTS: usize = 4;
+
 #[derive(Component)]
 pub struct Lava;
 
@@ -57,37 +42,55 @@ pub struct ItemSpawner {
     pub item: Item,
     pub timer: Timer,
     pub spawned: usize,
+    /// Items that were lost and will be spawned again as soon as the spawner is empty,
+    /// without waiting for the timer.
+    pub returning: usize,
 }
 impl ItemSpawner {
     fn new(item: Item, secs: f32) -> Self {
-        let mut timer = Timer::new(Duration::from_secs_f32(secs), TimerMode::Once);
-
-        // make the first tick finish the timer
-        timer.set_elapsed(Duration::from_secs_f32(secs - f32::EPSILON));
-
-        Self {
+        let mut spawner = Self {
             item,
-            timer,
+            timer: Timer::new(Duration::from_secs_f32(secs), TimerMode::Once),
             spawned: 0,
+            returning: 0,
+        };
+        spawner.finish_next_tick();
+        spawner
+    }
+
+    /// Makes the timer finish on the next tick, spawning the item.
+    pub fn finish_next_tick(&mut self) {
+        let duration = self.timer.duration();
+        self.timer
+            .set_elapsed(duration.saturating_sub(Duration::from_nanos(1)));
+    }
+
+    /// Returns an item that came from this spawner and was lost, so that it is spawned
+    /// again straight away, or as soon as the item currently on the spawner is taken.
+    pub fn return_item(&mut self) {
+        if self.timer.finished() {
+            self.returning += 1;
+        } else {
+            self.finish_next_tick();
         }
     }
 }
 
-#[derive(Component, Clone, Copy, PartialEq, Eq)]
+/// The spawner that an item came from.
+#[derive(Component)]
+pub struct ItemSource(pub Entity);
+
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug, Deserialize)]
 pub enum Item {
-    TowerKit,
+    TowerKit(TowerKind),
     LaserAmmo,
 }
 impl Display for Item {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "{}",
-            match self {
-                Item::TowerKit => "Tower Kit",
-                Item::LaserAmmo => "Ammo",
-            }
-        )
+        match self {
+            Item::TowerKit(kind) => write!(f, "{} Kit", kind),
+            Item::LaserAmmo => write!(f, "Ammo"),
+        }
     }
 }
 
@@ -157,112 +160,148 @@ impl FromWorld for PathMaterial {
 
 impl Plugin for MapPlugin {
     fn build(&self, app: &mut App) {
-        app.init_resource::<PathMaterial>()
-            .add_systems(OnEnter(GameState::Playing), spawn_map)
+        app.add_systems(OnEnter(GameState::Playing), spawn_map.after(rng::seed))
             // This must run before `TnuaPipelineStages::Sensors` or the player's movement
             // will not match up with the moving platform.
             .add_systems(
-                Update,
+                FixedUpdate,
                 moving_floor
                     .run_if(in_state(GameState::Playing))
                     .before(TnuaPipelineStages::Sensors),
             )
-            .add_systems(Update, item_spawner.run_if(in_state(GameState::Playing)))
             .add_systems(
-                Update,
+                FixedUpdate,
+                item_spawner.run_if(in_state(GameState::Playing)),
+            )
+            .add_systems(
+                FixedUpdate,
                 item_spawner_reset.run_if(in_state(GameState::Playing)),
             )
+            // Items are moved along with their colliders, so this is part of the
+            // simulation even though it's only for show.
             .add_systems(
-                Update,
+                FixedUpdate,
                 item_idle_movement.run_if(in_state(GameState::Playing)),
             );
     }
 }
 
-pub fn map_to_world(pos: UVec2) -> Vec3 {
+/// Models and decorations for the map, which aren't needed to simulate the game.
+pub struct MapPresentationPlugin;
+
+impl Plugin for MapPresentationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PathMaterial>()
+            .add_systems(OnEnter(GameState::Playing), spawn_path)
+            .add_systems(
+                Update,
+                spawn_floor_model.run_if(in_state(GameState::Playing)),
+            )
+            .add_systems(
+                Update,
+                spawn_item_model.run_if(in_state(GameState::Playing)),
+            );
+    }
+}
+
+pub fn map_to_world(level: &Level, pos: UVec2) -> Vec3 {
     Vec3::new(
-        (MAP_COLS as i32 / -2 + pos.x as i32) as f32 * TILE_SIZE.x,
+        (level.size.x as i32 / -2 + pos.x as i32) as f32 * TILE_SIZE.x,
         0.,
-        (MAP_ROWS as i32 / -2 + pos.y as i32) as f32 * TILE_SIZE.z,
+        (level.size.y as i32 / -2 + pos.y as i32) as f32 * TILE_SIZE.z,
     )
 }
 
-fn spawn_map(
-    mut commands: Commands,
-    models: Res<Models>,
-    mut meshes: ResMut<Assets<Mesh>>,
-    path_material: Res<PathMaterial>,
-) {
-    let mut rng = thread_rng();
-
-    let handles = [&models.tile1, &models.tile2, &models.tile3, &models.tile4];
-    let item_spawner_handle = &models.item_spawner;
-
-    for (row, row_val) in MAP.iter().enumerate() {
-        for (col, col_val) in row_val.iter().enumerate() {
-            if *col_val == 0 {
-                continue;
-            }
-
-            if *col_val == 2 {
-                continue;
-            }
+fn spawn_map(mut commands: Commands, level: Res<Level>, mut rng: ResMut<GameRng>) {
+    let positions = level
+        .floor_tiles()
+        .chain(level.moving_floors.iter().map(|floor| floor.waypoints[0]))
+        .chain(level.item_spawners.iter().map(|spawner| spawner.pos));
+
+    for pos in positions {
+        let rotation = Quat::from_rotation_y(rng.gen_range(0..3) as f32 * FRAC_PI_2);
+
+        let mut cmds = commands.spawn((
+            Floor,
+            Name::new("Floor"),
+            Transform::from_translation(map_to_world(&level, pos) + Vec3::Y * -0.5)
+                .with_rotation(rotation),
+            TilePos(pos),
+            FloorVariant(rng.gen_range(0..FLOOR_VARIANTS)),
+            Collider::cuboid(TILE_SIZE.x / 2., TILE_SIZE.y / 2., TILE_SIZE.x / 2.),
+            ActiveEvents::COLLISION_EVENTS,
+            DespawnOnReset,
+        ));
+
+        if let Some(spawner) = level.item_spawners.iter().find(|s| s.pos == pos) {
+            cmds.insert((
+                ItemSpawner::new(spawner.item, spawner.interval),
+                Name::new(match spawner.item {
+                    Item::TowerKit(kind) => format!("{}TowerKitSpawner", kind),
+                    Item::LaserAmmo => "LaserAmmoSpawner".to_string(),
+                }),
+            ));
+        }
 
-            let pos = UVec2::new(col as u32, row as u32);
-
-            let handle = if *col_val == 3 || *col_val == 4 {
-                item_spawner_handle
-            } else {
-                *handles.choose(&mut rng).unwrap()
-            };
-
-            let mut cmds = commands.spawn((
-                Floor,
-                SceneRoot(handle.clone()),
-                Transform::from_translation(map_to_world(pos) + Vec3::Y * -0.5).with_rotation(
-                    Quat::from_rotation_y(rng.gen_range(0..3) as f32 * FRAC_PI_2),
-                ),
-                TilePos(pos),
-                Collider::cuboid(TILE_SIZE.x / 2., TILE_SIZE.y / 2., TILE_SIZE.x / 2.),
-                ActiveEvents::COLLISION_EVENTS,
-                OutlineVolume {
-                    width: 3.0,
-                    colour: Color::hsla(160., 0.9, 0.5, 1.0),
-                    visible: true,
+        if let Some(floor) = level.moving_floors.iter().find(|f| f.waypoints[0] == pos) {
+            cmds.insert((
+                MovingFloor {
+                    waypoints: floor.waypoints.clone(),
+                    index: 0,
+                    speed: floor.speed,
+                    state: MovingFloorState::Dwell,
+                    direction: MovingFloorDirection::Forward,
+                    dwell_timer: Timer::new(Duration::from_secs_f32(floor.dwell), TimerMode::Once),
                 },
-                AsyncSceneInheritOutline::default(),
-                DespawnOnReset,
+                RigidBody::KinematicVelocityBased,
+                Velocity::default(),
             ));
+        }
+    }
 
-            if *col_val == 3 {
-                cmds.insert(ItemSpawner::new(Item::TowerKit, 30.0));
-                cmds.insert(Name::new("TowerKitSpawner"));
-            } else if *col_val == 4 {
-                cmds.insert(ItemSpawner::new(Item::LaserAmmo, 2.0));
-                cmds.insert(Name::new("LaserAmmoSpawner"));
-            } else {
-                cmds.insert(Name::new("Floor"));
-            }
+    commands.spawn((
+        Lava,
+        Transform::from_translation(Vec3::new(0., LAVA_DEPTH, 0.)),
+        Collider::halfspace(Vec3::Y).unwrap(),
+        Sensor,
+        DespawnOnReset,
+    ));
+}
 
-            // XXX
-            if col == 5 && row == 12 {
-                cmds.insert((
-                    MovingFloor {
-                        waypoints: vec![UVec2::new(5, 12), UVec2::new(10, 12)],
-                        index: 0,
-                        speed: 5.,
-                        state: MovingFloorState::Dwell,
-                        direction: MovingFloorDirection::Forward,
-                        dwell_timer: Timer::new(Duration::from_secs_f32(1.), TimerMode::Once),
-                    },
-                    RigidBody::KinematicVelocityBased,
-                    Velocity::default(),
-                ));
-            }
-        }
+fn spawn_floor_model(
+    mut commands: Commands,
+    query: Query<(Entity, &FloorVariant, Has<ItemSpawner>), Added<Floor>>,
+    models: Res<Models>,
+) {
+    let handles: [&Handle<Scene>; FLOOR_VARIANTS] =
+        [&models.tile1, &models.tile2, &models.tile3, &models.tile4];
+
+    for (entity, variant, is_spawner) in query.iter() {
+        let handle = if is_spawner {
+            &models.item_spawner
+        } else {
+            handles[variant.0]
+        };
+
+        commands.entity(entity).insert((
+            SceneRoot(handle.clone()),
+            OutlineVolume {
+                width: 3.0,
+                colour: Color::hsla(160., 0.9, 0.5, 1.0),
+                visible: true,
+            },
+            AsyncSceneInheritOutline::default(),
+        ));
     }
+}
 
-    for window in PATH.windows(2) {
+fn spawn_path(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    path_material: Res<PathMaterial>,
+    level: Res<Level>,
+) {
+    for window in level.path.windows(2) {
         let (start, end) = (window[0], window[1]);
 
         let mut xs = [start.x, end.x];
@@ -286,24 +325,17 @@ fn spawn_map(
                 Name::new("PathDot"),
                 Mesh3d(meshes.add(Cuboid::new(0.25, 0.25, 0.25))),
                 MeshMaterial3d(path_material.0.clone()),
-                Transform::from_translation(map_to_world(path_tile)),
+                Transform::from_translation(map_to_world(&level, path_tile)),
                 DespawnOnReset,
             ));
         }
     }
-
-    commands.spawn((
-        Lava,
-        Transform::from_translation(Vec3::new(0., LAVA_DEPTH, 0.)),
-        Collider::halfspace(Vec3::Y).unwrap(),
-        Sensor,
-        DespawnOnReset,
-    ));
 }
 
 fn moving_floor(
     mut query: Query<(&Transform, &mut Velocity, &mut MovingFloor), With<MovingFloor>>,
     time: Res<Time>,
+    level: Res<Level>,
 ) {
     for (transform, mut velocity, mut floor) in query.iter_mut() {
         match floor.state {
@@ -316,7 +348,7 @@ fn moving_floor(
             }
             MovingFloorState::Move => {
                 if let Some(next_waypoint) = floor.next_waypoint() {
-                    let world = map_to_world(next_waypoint) + Vec3::Y * -0.5;
+                    let world = map_to_world(&level, next_waypoint) + Vec3::Y * -0.5;
                     let diff = world - transform.translation;
                     let dist = diff.length();
 
@@ -349,7 +381,6 @@ fn item_spawner(
     mut commands: Commands,
     mut query: Query<(Entity, &mut ItemSpawner)>,
     time: Res<Time>,
-    models: Res<Models>,
 ) {
     for (entity, mut item_spawner) in query.iter_mut() {
         item_spawner.timer.tick(time.delta());
@@ -362,20 +393,11 @@ fn item_spawner(
         let item = commands
             .spawn((
                 item_spawner.item,
+                ItemSource(entity),
                 Name::new("Item"),
-                SceneRoot(match item_spawner.item {
-                    Item::LaserAmmo => models.laser_ammo.clone(),
-                    Item::TowerKit => models.tower_kit.clone(),
-                }),
                 Transform::from_xyz(0., 1.0, 0.),
                 Collider::ball(0.6),
                 Sensor,
-                OutlineVolume {
-                    width: 3.0,
-                    colour: Color::hsla(160., 0.9, 0.5, 1.0),
-                    visible: true,
-                },
-                AsyncSceneInheritOutline::default(),
             ))
             .id();
 
@@ -383,13 +405,41 @@ fn item_spawner(
     }
 }
 
+fn spawn_item_model(
+    mut commands: Commands,
+    query: Query<(Entity, &Item), Added<Item>>,
+    models: Res<Models>,
+) {
+    for (entity, item) in query.iter() {
+        commands.entity(entity).insert((
+            SceneRoot(match item {
+                Item::LaserAmmo => models.laser_ammo.clone(),
+                Item::TowerKit(_) => models.tower_kit.clone(),
+            }),
+            OutlineVolume {
+                width: 3.0,
+                colour: Color::hsla(160., 0.9, 0.5, 1.0),
+                visible: true,
+            },
+            AsyncSceneInheritOutline::default(),
+        ));
+    }
+}
+
 fn item_spawner_reset(
     mut query: Query<(&mut ItemSpawner, &Children), Changed<Children>>,
     item_query: Query<(), With<Item>>,
 ) {
     for (mut item_spawner, children) in query.iter_mut() {
-        if item_query.iter_many(children).count() == 0 {
-            item_spawner.timer.reset();
+        if item_query.iter_many(children).count() > 0 {
+            continue;
+        }
+
+        item_spawner.timer.reset();
+
+        if item_spawner.returning > 0 {
+            item_spawner.returning -= 1;
+            item_spawner.finish_next_tick();
         }
     }
 }
@@ -398,14 +448,17 @@ fn item_idle_movement(
     mut query: Query<(&Parent, &mut Transform), With<Item>>,
     spawner_query: Query<&ItemSpawner>,
     time: Res<Time>,
+    tick: Res<RunTick>,
 ) {
+    let elapsed = tick.0 as f32 * time.delta_secs();
+
     for (parent, mut transform) in query.iter_mut() {
         // Only animate items while they are on the spawner
         if spawner_query.get(**parent).is_err() {
             continue;
         }
 
-        transform.translation.y = 1.0 + (time.elapsed_secs_wrapped() * 2.).sin() * 0.05;
+        transform.translation.y = 1.0 + (elapsed * 2.).sin() * 0.05;
         transform.rotate_y(time.delta_secs() * 0.3);
     }
 }
@@ -4,8 +4,9 @@ use bevy_mod_outline::{
 };
 
 use crate::{
-    map::{ItemSpawner, MovingFloor, PlacedTower},
-    GrabbedItem, Item, SelectedItem, SelectedTile,
+    map::{Item, ItemSpawner, MovingFloor, PlacedTower},
+    player::{GrabbedItem, SelectedItem, SelectedTile},
+    tower::TowerLevel,
 };
 
 pub struct OutlinePlugin;
@@ -28,6 +29,7 @@ fn update(
     grabbed_item_query: Query<&Item, With<GrabbedItem>>,
     invalid_tile_query: Query<(), Or<(With<MovingFloor>, With<PlacedTower>, With<ItemSpawner>)>>,
     placed_tower_query: Query<&PlacedTower>,
+    tower_level_query: Query<&TowerLevel>,
     mut outline_query: Query<&mut OutlineVolume>,
 ) {
     if selection_changed_query.is_empty()
@@ -59,11 +61,21 @@ fn update(
                 }
             }
         }
-        Ok(Item::TowerKit) => {
-            // Outline valid tiles for tower placement
+        Ok(Item::TowerKit(_)) => {
+            // Outline valid tiles for tower placement, or towers that can be upgraded
 
             if let Some(entity) = tile.0 {
-                if invalid_tile_query.get(entity).is_err() {
+                if let Ok(placed_tower) = placed_tower_query.get(entity) {
+                    let upgradable = tower_level_query
+                        .get(placed_tower.0)
+                        .is_ok_and(|level| level.can_upgrade());
+
+                    if upgradable {
+                        if let Ok(mut outline) = outline_query.get_mut(placed_tower.0) {
+                            outline.visible = true;
+                        }
+                    }
+                } else if invalid_tile_query.get(entity).is_err() {
                     if let Ok(mut outline) = outline_query.get_mut(entity) {
                         outline.visible = true;
                     }
@@ -0,0 +1,322 @@
+use bevy::prelude::*;
+use bevy_alt_ui_navigation_lite::prelude::*;
+
+use crate::{
+    loading::Fonts,
+    settings::{
+        cycle_volume, HealthBarSetting, HealthBarSettingButtonText, MusicSetting,
+        MusicSettingButtonText, SfxSetting, SfxSettingButtonText,
+    },
+    ui::{
+        buttons, controls, BUTTON_TEXT, CONTAINER_BACKGROUND, NORMAL_BUTTON, OVERLAY, TITLE_TEXT,
+    },
+    GameState,
+};
+
+pub struct PausePlugin;
+
+/// Whether the game is paused while playing. Gameplay runs in `FixedUpdate`, which stops
+/// ticking while virtual time is paused.
+#[derive(SubStates, Debug, Clone, Copy, Default, Eq, PartialEq, Hash)]
+#[source(GameState = GameState::Playing)]
+pub enum PlayState {
+    #[default]
+    Running,
+    Paused,
+    /// Paused, with the settings open.
+    Settings,
+}
+
+/// Whether time was already paused when the game stopped running, as it can be by the
+/// replay controls, so that it stays paused once the menu closes.
+#[derive(Resource, Default)]
+struct PausedBeforeMenu(bool);
+
+#[derive(Component)]
+struct PauseMenuMarker;
+
+#[derive(Component, Debug)]
+enum PauseButton {
+    Resume,
+    Restart,
+    Settings,
+    Quit,
+    Sfx,
+    Music,
+    HealthBars,
+    Back,
+}
+
+impl Plugin for PausePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_sub_state::<PlayState>()
+            .init_resource::<PausedBeforeMenu>()
+            .add_systems(Update, toggle.run_if(in_state(GameState::Playing)))
+            .add_systems(
+                Update,
+                (button_actions, buttons.after(NavRequestSystem))
+                    .run_if(in_state(GameState::Playing)),
+            )
+            .add_systems(OnExit(PlayState::Running), pause_time)
+            .add_systems(OnEnter(PlayState::Running), resume_time)
+            .add_systems(OnExit(GameState::Playing), unpause_time)
+            .add_systems(OnEnter(PlayState::Paused), setup_menu)
+            .add_systems(OnExit(PlayState::Paused), cleanup_menu)
+            .add_systems(OnEnter(PlayState::Settings), setup_settings)
+            .add_systems(OnExit(PlayState::Settings), cleanup_menu)
+            .add_systems(OnEnter(GameState::Restarting), restart);
+    }
+}
+
+/// Escape or the start button opens the pause menu, or backs out of it.
+fn toggle(
+    keys: Res<ButtonInput<KeyCode>>,
+    gamepads: Query<&Gamepad>,
+    state: Res<State<PlayState>>,
+    mut next_state: ResMut<NextState<PlayState>>,
+) {
+    let pressed = keys.just_pressed(KeyCode::Escape)
+        || gamepads
+            .iter()
+            .any(|gamepad| gamepad.just_pressed(GamepadButton::Start));
+    if !pressed {
+        return;
+    }
+
+    next_state.set(match state.get() {
+        PlayState::Running => PlayState::Paused,
+        PlayState::Paused => PlayState::Running,
+        PlayState::Settings => PlayState::Paused,
+    });
+}
+
+fn pause_time(mut time: ResMut<Time<Virtual>>, mut paused_before: ResMut<PausedBeforeMenu>) {
+    paused_before.0 = time.is_paused();
+    time.pause();
+}
+
+fn resume_time(mut time: ResMut<Time<Virtual>>, paused_before: Res<PausedBeforeMenu>) {
+    if !paused_before.0 {
+        time.unpause();
+    }
+}
+
+/// Leaving the game always unpauses, whatever state the menu was opened in.
+fn unpause_time(mut time: ResMut<Time<Virtual>>, mut paused_before: ResMut<PausedBeforeMenu>) {
+    paused_before.0 = false;
+    time.unpause();
+}
+
+fn restart(mut next_state: ResMut<NextState<GameState>>) {
+    next_state.set(GameState::Playing);
+}
+
+/// Spawns a container for a menu, covering the game with an overlay.
+fn spawn_container(commands: &mut Commands, fonts: &Fonts, title: &str) -> Entity {
+    let root = commands
+        .spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.),
+                height: Val::Percent(100.),
+                flex_direction: FlexDirection::Column,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                row_gap: Val::Px(20.),
+                ..default()
+            },
+            BackgroundColor(OVERLAY.into()),
+            PauseMenuMarker,
+        ))
+        .id();
+
+    let container = commands
+        .spawn((
+            Node {
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                padding: UiRect::all(Val::Px(20.)),
+                ..default()
+            },
+            BackgroundColor(CONTAINER_BACKGROUND.into()),
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                Text::new(title),
+                TextFont {
+                    font: fonts.main.clone(),
+                    font_size: 50.0,
+                    ..default()
+                },
+                TextColor(TITLE_TEXT.into()),
+                Node {
+                    margin: UiRect {
+                        bottom: Val::Px(10.0),
+                        ..default()
+                    },
+                    ..default()
+                },
+            ));
+        })
+        .id();
+
+    commands.entity(root).add_child(container);
+
+    container
+}
+
+fn spawn_button(
+    commands: &mut Commands,
+    fonts: &Fonts,
+    button: PauseButton,
+    text: impl Bundle,
+) -> Entity {
+    commands
+        .spawn((
+            Button,
+            Node {
+                width: Val::Px(250.0),
+                height: Val::Px(45.0),
+                margin: UiRect::all(Val::Px(5.0)),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            BackgroundColor(NORMAL_BUTTON.into()),
+            Focusable::default(),
+            button,
+        ))
+        .with_child((
+            text,
+            TextFont {
+                font: fonts.main.clone(),
+                font_size: 25.0,
+                ..default()
+            },
+            TextColor(BUTTON_TEXT.into()),
+        ))
+        .id()
+}
+
+fn setup_menu(mut commands: Commands, fonts: Res<Fonts>) {
+    let container = spawn_container(&mut commands, &fonts, "PAUSED");
+
+    let buttons = [
+        (PauseButton::Resume, "RESUME"),
+        (PauseButton::Restart, "RESTART RUN"),
+        (PauseButton::Settings, "SETTINGS"),
+        (PauseButton::Quit, "QUIT TO MENU"),
+    ]
+    .map(|(button, text)| spawn_button(&mut commands, &fonts, button, Text::new(text)));
+
+    commands.entity(container).add_children(&buttons);
+
+    let controls_container = commands
+        .spawn(Node {
+            column_gap: Val::Px(20.),
+            ..default()
+        })
+        .with_children(|parent| controls(parent, &fonts))
+        .id();
+
+    commands.entity(container).add_child(controls_container);
+}
+
+fn setup_settings(
+    mut commands: Commands,
+    fonts: Res<Fonts>,
+    sfx: Res<SfxSetting>,
+    music: Res<MusicSetting>,
+    health_bars: Res<HealthBarSetting>,
+) {
+    let container = spawn_container(&mut commands, &fonts, "SETTINGS");
+
+    let sfx_button = spawn_button(
+        &mut commands,
+        &fonts,
+        PauseButton::Sfx,
+        (Text::new(sfx.label()), SfxSettingButtonText),
+    );
+    let music_button = spawn_button(
+        &mut commands,
+        &fonts,
+        PauseButton::Music,
+        (Text::new(music.label()), MusicSettingButtonText),
+    );
+    let health_bars_button = spawn_button(
+        &mut commands,
+        &fonts,
+        PauseButton::HealthBars,
+        (Text::new(health_bars.label()), HealthBarSettingButtonText),
+    );
+    let back_button = spawn_button(&mut commands, &fonts, PauseButton::Back, Text::new("BACK"));
+
+    commands.entity(container).add_children(&[
+        sfx_button,
+        music_button,
+        health_bars_button,
+        back_button,
+    ]);
+}
+
+fn button_actions(
+    buttons: Query<&PauseButton>,
+    mut events: EventReader<NavEvent>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut next_play_state: ResMut<NextState<PlayState>>,
+    mut sfx_setting: ResMut<SfxSetting>,
+    mut music_setting: ResMut<MusicSetting>,
+    mut health_bar_setting: ResMut<HealthBarSetting>,
+    mut text_queries: ParamSet<(
+        Query<&mut Text, With<SfxSettingButtonText>>,
+        Query<&mut Text, With<MusicSettingButtonText>>,
+        Query<&mut Text, With<HealthBarSettingButtonText>>,
+    )>,
+) {
+    for button in events.nav_iter().activated_in_query(&buttons) {
+        match button {
+            PauseButton::Resume => {
+                next_play_state.set(PlayState::Running);
+            }
+            PauseButton::Restart => {
+                next_state.set(GameState::Restarting);
+            }
+            PauseButton::Settings => {
+                next_play_state.set(PlayState::Settings);
+            }
+            PauseButton::Quit => {
+                next_state.set(GameState::MainMenu);
+            }
+            PauseButton::Sfx => {
+                **sfx_setting = cycle_volume(**sfx_setting);
+
+                for mut text in text_queries.p0().iter_mut() {
+                    text.0 = sfx_setting.label();
+                }
+            }
+            PauseButton::Music => {
+                **music_setting = cycle_volume(**music_setting);
+
+                for mut text in text_queries.p1().iter_mut() {
+                    text.0 = music_setting.label();
+                }
+            }
+            PauseButton::HealthBars => {
+                *health_bar_setting = health_bar_setting.next();
+
+                for mut text in text_queries.p2().iter_mut() {
+                    text.0 = health_bar_setting.label();
+                }
+            }
+            PauseButton::Back => {
+                next_play_state.set(PlayState::Paused);
+            }
+        }
+    }
+}
+
+fn cleanup_menu(mut commands: Commands, query: Query<Entity, With<PauseMenuMarker>>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
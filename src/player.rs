@@ -0,0 +1,911 @@
+use std::collections::VecDeque;
+
+use bevy::{audio::Volume, prelude::*};
+use bevy_rapier3d::prelude::*;
+use bevy_tnua::prelude::*;
+use bevy_tnua_rapier3d::{TnuaRapier3dIOBundle, TnuaRapier3dPlugin, TnuaRapier3dSensorShape};
+use bevy_two_entities::tuple::{TupleQueryExt, TupleQueryMutExt};
+use leafwing_input_manager::{plugin::InputManagerSystem, prelude::*};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    currency::{price, Currency},
+    level::Level,
+    loading::{Models, Sounds},
+    map::{
+        map_to_world, Floor, Item, ItemSource, ItemSpawner, Lava, MovingFloor, PlacedTower, TilePos,
+    },
+    pause::PlayState,
+    replay::Playback,
+    settings::SfxSetting,
+    stats::RunStats,
+    tower::{
+        Ammo, SpawnTowerEvent, TargetPriority, Tower, TowerKind, TowerLevel, UpgradeTowerEvent,
+    },
+    AfterPhysics, DespawnOnReset, GameState,
+};
+
+/// How many of the tiles that the player most recently stood on are remembered.
+const RESPAWN_HISTORY_LEN: usize = 8;
+/// Seconds after respawning during which the player can't fall into lava again.
+const GHOST_SECS: f32 = 1.5;
+const FLASH_SECS: f32 = 0.4;
+
+pub struct PlayerPlugin;
+
+#[derive(Component)]
+pub struct Player;
+
+#[derive(Actionlike, PartialEq, Eq, Clone, Copy, Hash, Debug, Reflect)]
+pub enum Action {
+    #[actionlike(DualAxis)]
+    Run,
+    Jump,
+    Grab,
+}
+
+/// The player's input for the current tick. Gameplay reads this rather than the
+/// `ActionState<Action>`, so that a replay can supply it instead.
+#[derive(Component, Default, Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub struct PlayerInput {
+    pub run: Vec2,
+    pub jump: bool,
+    /// Whether grab was pressed since the last tick.
+    pub grab: bool,
+}
+
+/// Whether grab has been pressed since the last tick. `just_pressed` only lasts for a
+/// frame, and frames and ticks don't line up, so presses are held here until a tick
+/// reads them.
+#[derive(Component, Default)]
+struct PendingGrab(bool);
+
+/// Systems that set the player's `PlayerInput` for the tick.
+#[derive(Debug, Hash, PartialEq, Eq, Clone, SystemSet)]
+pub struct PlayerInputSet;
+
+#[derive(Component)]
+pub struct Cursor;
+
+#[derive(Component)]
+pub struct TileProbe;
+
+#[derive(Component)]
+pub struct ItemProbe;
+
+/// Static floor tiles that the player recently stood on, most recent last. The player
+/// respawns on one of these after falling into lava.
+#[derive(Component)]
+pub struct RespawnHistory(VecDeque<UVec2>);
+impl RespawnHistory {
+    pub fn new(tile: UVec2) -> Self {
+        Self(VecDeque::from([tile]))
+    }
+
+    pub fn push(&mut self, tile: UVec2) {
+        self.0.retain(|t| *t != tile);
+        if self.0.len() == RESPAWN_HISTORY_LEN {
+            self.0.pop_front();
+        }
+        self.0.push_back(tile);
+    }
+
+    /// The most recent tile that is still safe to respawn on.
+    pub fn latest(&self, safe: impl Fn(UVec2) -> bool) -> Option<UVec2> {
+        self.0.iter().rev().copied().find(|tile| safe(*tile))
+    }
+}
+
+/// Whether the player is in the lava.
+#[derive(Component, Default)]
+pub struct InLava(pub bool);
+
+/// The player respawned recently, and lava can't catch them until the timer finishes.
+#[derive(Component)]
+pub struct Ghost(pub Timer);
+
+#[derive(Component)]
+struct PlayerModel;
+
+#[derive(Component)]
+struct RespawnFlash(Timer);
+
+#[derive(Component, Reflect)]
+pub struct SelectedTile(pub Option<Entity>);
+
+#[derive(Component, Default, Reflect)]
+pub struct SelectedItem(pub Option<Entity>);
+
+#[derive(Component)]
+pub struct GrabbedItem;
+
+#[derive(Event)]
+pub struct SpawnPlayerEvent(pub UVec2);
+
+impl Plugin for PlayerPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<SpawnPlayerEvent>()
+            .add_plugins(TnuaControllerPlugin::new(FixedUpdate))
+            .add_plugins(TnuaRapier3dPlugin::new(FixedUpdate))
+            .add_plugins(InputManagerPlugin::<Action>::default())
+            .configure_sets(
+                FixedUpdate,
+                PlayerInputSet.before(TnuaUserControlsSystemSet),
+            )
+            .add_systems(OnEnter(GameState::Playing), setup)
+            .add_systems(
+                FixedUpdate,
+                read_input
+                    .in_set(PlayerInputSet)
+                    .run_if(in_state(GameState::Playing))
+                    .run_if(not(resource_exists::<Playback>)),
+            )
+            .add_systems(
+                FixedUpdate,
+                (
+                    cursor,
+                    item_probe,
+                    spawn_player,
+                    track_respawn_history,
+                    lava_contact,
+                    lava.after(lava_contact),
+                    ghost,
+                    grab,
+                    build_tower,
+                    feed_tower,
+                    cycle_target_priority,
+                    buy_item,
+                )
+                    .after(PlayerInputSet)
+                    .distributive_run_if(in_state(GameState::Playing)),
+            )
+            .add_systems(
+                FixedUpdate,
+                apply_controls.in_set(TnuaUserControlsSystemSet),
+            )
+            // Presses made in the pause menu shouldn't carry over once it's closed.
+            .add_systems(
+                PreUpdate,
+                latch_grab
+                    .after(InputManagerSystem::Update)
+                    .run_if(in_state(PlayState::Running))
+                    .run_if(not(resource_exists::<Playback>)),
+            )
+            .add_systems(
+                Update,
+                (respawn_effect, respawn_flash, ghost_blink).run_if(in_state(GameState::Playing)),
+            )
+            .add_systems(
+                PostUpdate,
+                reset_item_on_grab
+                    .in_set(AfterPhysics)
+                    .run_if(in_state(GameState::Playing)),
+            );
+
+        #[cfg(feature = "inspector")]
+        {
+            app.register_type::<SelectedTile>();
+            app.register_type::<SelectedItem>();
+        }
+    }
+}
+
+fn setup(mut spawn_player_events: EventWriter<SpawnPlayerEvent>, level: Res<Level>) {
+    spawn_player_events.send(SpawnPlayerEvent(level.player_start));
+}
+
+fn latch_grab(mut query: Query<(&ActionState<Action>, &mut PendingGrab), With<Player>>) {
+    for (action_state, mut pending) in query.iter_mut() {
+        if action_state.just_pressed(&Action::Grab) {
+            pending.0 = true;
+        }
+    }
+}
+
+fn read_input(
+    mut query: Query<(&ActionState<Action>, &mut PendingGrab, &mut PlayerInput), With<Player>>,
+) {
+    for (action_state, mut pending, mut input) in query.iter_mut() {
+        *input = PlayerInput {
+            run: action_state.clamped_axis_pair(&Action::Run),
+            jump: action_state.pressed(&Action::Jump),
+            grab: pending.0,
+        };
+
+        pending.0 = false;
+    }
+}
+
+fn apply_controls(
+    input_query: Query<&PlayerInput, With<Player>>,
+    mut query: Query<&mut TnuaController>,
+) {
+    let Ok(input) = input_query.get_single() else {
+        return;
+    };
+
+    let direction = Vec3::new(input.run.x, 0., -input.run.y);
+    let turn_in_place = direction.x.abs() < 0.3 && direction.z.abs() < 0.3;
+
+    let normalized = direction.normalize_or_zero();
+    let with_speed = normalized * 4.3;
+
+    let jump = input.jump;
+
+    for mut controls in query.iter_mut() {
+        controls.basis(TnuaBuiltinWalk {
+            desired_velocity: if turn_in_place {
+                Vec3::ZERO
+            } else {
+                with_speed
+            },
+            desired_forward: direction.try_normalize().map(Dir3::new_unchecked),
+            float_height: 1.0,
+            cling_distance: 0.5,
+            acceleration: 50.0,
+            air_acceleration: 10.0,
+            turning_angvel: 5.0,
+            ..default()
+        });
+
+        if jump {
+            controls.action(TnuaBuiltinJump {
+                height: 2.0,
+                shorten_extra_gravity: 40.0,
+                ..default()
+            });
+        }
+    }
+}
+
+fn cursor(
+    mut collision_events: EventReader<CollisionEvent>,
+    cursor_query: Query<&Parent, With<Cursor>>,
+    floor_query: Query<Entity, With<Floor>>,
+    mut selected_tile_query: Query<&mut SelectedTile>,
+) {
+    for evt in collision_events.read() {
+        match evt {
+            CollisionEvent::Started(e1, e2, _) => {
+                let queries = (&cursor_query, &floor_query);
+                let Some((cursor, floor_entity)) = queries.get_both(*e1, *e2) else {
+                    continue;
+                };
+
+                if let Ok(mut selected_tile) = selected_tile_query.get_mut(cursor.get()) {
+                    selected_tile.0 = Some(floor_entity);
+                }
+            }
+            CollisionEvent::Stopped(e1, e2, _) => {
+                let queries = (&cursor_query, &floor_query);
+                let Some((cursor, _floor_entity)) = queries.get_both(*e1, *e2) else {
+                    continue;
+                };
+
+                if let Ok(mut selected_tile) = selected_tile_query.get_mut(cursor.get()) {
+                    selected_tile.0 = None;
+                }
+            }
+        }
+    }
+}
+
+fn item_probe(
+    mut collision_events: EventReader<CollisionEvent>,
+    probe_query: Query<&Parent, With<ItemProbe>>,
+    item_query: Query<Entity, With<Item>>,
+    mut selected_item_query: Query<&mut SelectedItem>,
+) {
+    for evt in collision_events.read() {
+        match evt {
+            CollisionEvent::Started(e1, e2, _) => {
+                let queries = (&probe_query, &item_query);
+                let Some((probe_entity, item_entity)) = queries.get_both(*e1, *e2) else {
+                    continue;
+                };
+
+                if let Ok(mut selected_item) = selected_item_query.get_mut(probe_entity.get()) {
+                    selected_item.0 = Some(item_entity);
+                }
+            }
+            CollisionEvent::Stopped(e1, e2, _) => {
+                let queries = (&probe_query, &item_query);
+                if !queries.both(*e1, *e2) {
+                    continue;
+                };
+
+                for mut selected_item in selected_item_query.iter_mut() {
+                    selected_item.0 = None;
+                }
+            }
+        }
+    }
+}
+
+fn track_respawn_history(
+    mut collision_events: EventReader<CollisionEvent>,
+    probe_query: Query<&Parent, With<TileProbe>>,
+    floor_query: Query<&TilePos, (With<Floor>, Without<MovingFloor>)>,
+    mut history_query: Query<&mut RespawnHistory>,
+) {
+    for evt in collision_events.read() {
+        if let CollisionEvent::Started(e1, e2, _) = evt {
+            let queries = (&probe_query, &floor_query);
+            let Some((probe_entity, tile_pos)) = queries.get_both(*e1, *e2) else {
+                continue;
+            };
+
+            if let Ok(mut history) = history_query.get_mut(probe_entity.get()) {
+                history.push(tile_pos.0);
+            }
+        }
+    }
+}
+
+fn lava_contact(
+    mut collision_events: EventReader<CollisionEvent>,
+    lava_query: Query<&Lava>,
+    mut player_query: Query<&mut InLava, With<Player>>,
+) {
+    for evt in collision_events.read() {
+        let (e1, e2, started) = match evt {
+            CollisionEvent::Started(e1, e2, _) => (e1, e2, true),
+            CollisionEvent::Stopped(e1, e2, _) => (e1, e2, false),
+        };
+
+        let mut queries = (&lava_query, &mut player_query);
+        if let Some((_, mut in_lava)) = queries.get_both_mut(*e1, *e2) {
+            in_lava.0 = started;
+        }
+    }
+}
+
+/// Moves a player that fell into the lava back to the most recent tile they stood on that
+/// doesn't have a tower on it, and briefly makes them a ghost that lava can't catch.
+/// If every one of those tiles has a tower, they go back to the start, or the nearest
+/// tile to it without a tower.
+fn lava(
+    mut commands: Commands,
+    mut player_query: Query<
+        (
+            Entity,
+            &RespawnHistory,
+            &InLava,
+            &Children,
+            &mut Transform,
+            &mut Velocity,
+        ),
+        (With<Player>, Without<Ghost>),
+    >,
+    item_query: Query<(Entity, Option<&ItemSource>), With<Item>>,
+    mut item_spawner_query: Query<&mut ItemSpawner>,
+    safe_tile_query: Query<&TilePos, (With<Floor>, Without<MovingFloor>, Without<PlacedTower>)>,
+    level: Res<Level>,
+    mut stats: ResMut<RunStats>,
+) {
+    for (entity, history, in_lava, children, mut transform, mut velocity) in player_query.iter_mut()
+    {
+        if !in_lava.0 {
+            continue;
+        }
+
+        let safe_tiles: Vec<UVec2> = safe_tile_query.iter().map(|pos| pos.0).collect();
+        let pos = respawn_tile(history, level.player_start, &safe_tiles);
+
+        transform.translation = map_to_world(&level, pos) + Vec3::Y * 0.5;
+        *velocity = Velocity::zero();
+        stats.falls += 1;
+
+        commands
+            .entity(entity)
+            .insert(Ghost(Timer::from_seconds(GHOST_SECS, TimerMode::Once)));
+
+        for (item_entity, source) in item_query.iter_many(children) {
+            commands.entity(item_entity).despawn_recursive();
+
+            if let Some(mut item_spawner) =
+                source.and_then(|source| item_spawner_query.get_mut(source.0).ok())
+            {
+                item_spawner.return_item();
+            }
+        }
+    }
+}
+
+/// The most recent safe tile in the history, or else the safe tile closest to the start.
+fn respawn_tile(history: &RespawnHistory, start: UVec2, safe_tiles: &[UVec2]) -> UVec2 {
+    history
+        .latest(|tile| safe_tiles.contains(&tile))
+        .or_else(|| {
+            // Ties are broken by position so that the same tile is always picked.
+            safe_tiles.iter().copied().min_by_key(|tile| {
+                (
+                    tile.as_ivec2().distance_squared(start.as_ivec2()),
+                    tile.y,
+                    tile.x,
+                )
+            })
+        })
+        .unwrap_or(start)
+}
+
+fn ghost(mut commands: Commands, mut query: Query<(Entity, &mut Ghost)>, time: Res<Time>) {
+    for (entity, mut ghost) in query.iter_mut() {
+        if ghost.0.tick(time.delta()).finished() {
+            commands.entity(entity).remove::<Ghost>();
+        }
+    }
+}
+
+fn respawn_effect(
+    mut commands: Commands,
+    query: Query<&Transform, Added<Ghost>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    game_audio: Res<Sounds>,
+    audio_setting: Res<SfxSetting>,
+) {
+    for transform in query.iter() {
+        commands.spawn((
+            RespawnFlash(Timer::from_seconds(FLASH_SECS, TimerMode::Once)),
+            Name::new("RespawnFlash"),
+            Mesh3d(meshes.add(Sphere::new(0.5))),
+            MeshMaterial3d(materials.add(StandardMaterial {
+                base_color: Color::srgba(0.4, 0.9, 1.0, 0.6),
+                alpha_mode: AlphaMode::Blend,
+                unlit: true,
+                ..default()
+            })),
+            Transform::from_translation(transform.translation),
+            DespawnOnReset,
+        ));
+
+        commands.spawn((
+            AudioPlayer(game_audio.feed.clone()),
+            PlaybackSettings::DESPAWN.with_volume(Volume::new(**audio_setting as f32 / 100.)),
+        ));
+    }
+}
+
+fn respawn_flash(
+    mut commands: Commands,
+    mut query: Query<(
+        Entity,
+        &mut RespawnFlash,
+        &mut Transform,
+        &MeshMaterial3d<StandardMaterial>,
+    )>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    time: Res<Time>,
+) {
+    for (entity, mut flash, mut transform, material) in query.iter_mut() {
+        flash.0.tick(time.delta());
+        if flash.0.finished() {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+
+        let fraction = flash.0.fraction();
+        transform.scale = Vec3::splat(1. + fraction * 2.);
+        if let Some(material) = materials.get_mut(&material.0) {
+            material.base_color.set_alpha(0.6 * (1. - fraction));
+        }
+    }
+}
+
+/// Ghosts flicker.
+fn ghost_blink(
+    player_query: Query<(&Children, Option<&Ghost>), With<Player>>,
+    mut model_query: Query<&mut Visibility, With<PlayerModel>>,
+) {
+    for (children, ghost) in player_query.iter() {
+        let visible = match ghost {
+            Some(ghost) => (ghost.0.elapsed_secs() * 10.) as u32 % 2 == 0,
+            None => true,
+        };
+
+        let mut models = model_query.iter_many_mut(children);
+        while let Some(mut visibility) = models.fetch_next() {
+            visibility.set_if_neq(if visible {
+                Visibility::Inherited
+            } else {
+                Visibility::Hidden
+            });
+        }
+    }
+}
+
+fn spawn_player(
+    mut commands: Commands,
+    mut events: EventReader<SpawnPlayerEvent>,
+    models: Res<Models>,
+    level: Res<Level>,
+) {
+    for event in events.read() {
+        commands
+            .spawn((
+                Player,
+                Name::new("Player"),
+                Transform::from_translation(map_to_world(&level, event.0) + Vec3::Y * 0.5),
+                Visibility::default(),
+                RigidBody::Dynamic,
+                Velocity::default(),
+                Collider::capsule_y(0.30, 0.5),
+                ActiveEvents::COLLISION_EVENTS,
+                ExternalForce::default(),
+                ReadMassProperties::default(),
+                RespawnHistory::new(event.0),
+                InLava::default(),
+                SelectedTile(None),
+                SelectedItem(None),
+                PlayerInput::default(),
+                PendingGrab::default(),
+                InputManagerBundle::<Action> {
+                    action_state: ActionState::default(),
+                    input_map: InputMap::default()
+                        .with(Action::Jump, KeyCode::Space)
+                        .with(Action::Jump, GamepadButton::South)
+                        .with(Action::Grab, KeyCode::KeyR)
+                        .with(Action::Grab, GamepadButton::West)
+                        .with_dual_axis(Action::Run, GamepadStick::LEFT)
+                        .with_dual_axis(Action::Run, VirtualDPad::wasd())
+                        .with_dual_axis(Action::Run, VirtualDPad::arrow_keys()),
+                },
+                DespawnOnReset,
+            ))
+            .insert((
+                TnuaRapier3dIOBundle::default(),
+                TnuaController::default(),
+                TnuaRapier3dSensorShape(Collider::cylinder(0.0, 0.49)),
+            ))
+            .with_children(|parent| {
+                parent.spawn((
+                    PlayerModel,
+                    SceneRoot(models.player.clone()),
+                    Transform::from_xyz(0., -0.4, 0.),
+                ));
+
+                // probe for current tile
+                parent.spawn((
+                    TileProbe,
+                    Name::new("TileProbe"),
+                    Transform::default(),
+                    Collider::segment(Vec3::new(0., 0., 0.), Vec3::new(0.0, -2.0, 0.)),
+                    Sensor,
+                    ActiveEvents::COLLISION_EVENTS,
+                ));
+
+                // probe for current tile
+                parent.spawn((
+                    ItemProbe,
+                    Name::new("ItemProbe"),
+                    Transform::default(),
+                    Collider::segment(Vec3::new(0., -0.25, 0.), Vec3::new(0.0, -0.25, -1.0)),
+                    Sensor,
+                    ActiveEvents::COLLISION_EVENTS,
+                ));
+
+                // cursor
+                parent.spawn((
+                    Cursor,
+                    Name::new("Cursor"),
+                    Transform::from_xyz(0.0, -0.9, -1.5),
+                    Collider::segment(Vec3::new(0.0, 0., 0.), Vec3::new(0.0, -2.1, 0.)),
+                    Sensor,
+                    ActiveEvents::COLLISION_EVENTS,
+                ));
+            });
+    }
+}
+
+fn grab(
+    mut commands: Commands,
+    player_query: Query<(Entity, &Children, &PlayerInput, &SelectedItem), With<Player>>,
+    grabbed_item_query: Query<(), With<GrabbedItem>>,
+    mut item_query: Query<&Item>,
+    game_audio: Res<Sounds>,
+    audio_setting: Res<SfxSetting>,
+) {
+    let Ok((entity, children, input, selected_item)) = player_query.get_single() else {
+        return;
+    };
+
+    if !input.grab {
+        return;
+    }
+
+    let Some(selected_item) = selected_item.0 else {
+        return;
+    };
+    if item_query.get_mut(selected_item).is_err() {
+        return;
+    };
+
+    // player is already holding an item
+    if grabbed_item_query.iter_many(children).next().is_some() {
+        commands.spawn((
+            AudioPlayer(game_audio.bad.clone()),
+            PlaybackSettings::DESPAWN.with_volume(Volume::new(**audio_setting as f32 / 100.)),
+        ));
+
+        return;
+    }
+
+    commands
+        .entity(selected_item)
+        .set_parent(entity)
+        .remove::<Collider>()
+        .insert(GrabbedItem);
+}
+
+fn build_tower(
+    mut commands: Commands,
+    player_query: Query<(&Children, &PlayerInput, &SelectedTile), With<Player>>,
+    grabbed_item_query: Query<(Entity, &Item)>,
+    invalid_tile_query: Query<(), Or<(With<MovingFloor>, With<PlacedTower>, With<ItemSpawner>)>>,
+    placed_tower_query: Query<&PlacedTower>,
+    tower_query: Query<(&TowerKind, &TowerLevel)>,
+    game_audio: Res<Sounds>,
+    audio_setting: Res<SfxSetting>,
+    mut events: EventWriter<SpawnTowerEvent>,
+    mut upgrade_events: EventWriter<UpgradeTowerEvent>,
+) {
+    let Ok((children, input, selected_tile)) = player_query.get_single() else {
+        return;
+    };
+
+    if !input.grab {
+        return;
+    }
+
+    let Some((entity, item)) = grabbed_item_query.iter_many(children).next() else {
+        return;
+    };
+
+    let Item::TowerKit(kind) = *item else {
+        return;
+    };
+
+    let Some(selected_tile) = selected_tile.0 else {
+        commands.spawn((
+            AudioPlayer(game_audio.bad.clone()),
+            PlaybackSettings::DESPAWN.with_volume(Volume::new(**audio_setting as f32 / 100.)),
+        ));
+        return;
+    };
+
+    // Feeding a tower kit to an existing tower of the same kind upgrades it
+    if let Ok(placed_tower) = placed_tower_query.get(selected_tile) {
+        let upgradable = tower_query
+            .get(placed_tower.0)
+            .is_ok_and(|(tower_kind, level)| *tower_kind == kind && level.can_upgrade());
+        if !upgradable {
+            commands.spawn((
+                AudioPlayer(game_audio.bad.clone()),
+                PlaybackSettings::DESPAWN.with_volume(Volume::new(**audio_setting as f32 / 100.)),
+            ));
+            return;
+        }
+
+        commands.entity(entity).despawn_recursive();
+
+        upgrade_events.send(UpgradeTowerEvent(placed_tower.0));
+        return;
+    }
+
+    let invalid = invalid_tile_query.get(selected_tile).is_ok();
+    if invalid {
+        commands.spawn((
+            AudioPlayer(game_audio.bad.clone()),
+            PlaybackSettings::DESPAWN.with_volume(Volume::new(**audio_setting as f32 / 100.)),
+        ));
+        return;
+    }
+
+    commands.entity(entity).despawn_recursive();
+
+    events.send(SpawnTowerEvent(selected_tile, kind));
+}
+
+fn feed_tower(
+    mut commands: Commands,
+    player_query: Query<(&Children, &PlayerInput, &SelectedTile), With<Player>>,
+    grabbed_item_query: Query<(Entity, &Item)>,
+    placed_tower_query: Query<&PlacedTower>,
+    mut tower_query: Query<&mut Ammo, With<Tower>>,
+    game_audio: Res<Sounds>,
+    audio_setting: Res<SfxSetting>,
+    mut stats: ResMut<RunStats>,
+) {
+    let Ok((children, input, selected_tile)) = player_query.get_single() else {
+        return;
+    };
+
+    if !input.grab {
+        return;
+    }
+
+    let Some((entity, item)) = grabbed_item_query.iter_many(children).next() else {
+        return;
+    };
+
+    if *item != Item::LaserAmmo {
+        return;
+    }
+
+    let Some(selected_tile) = selected_tile.0 else {
+        commands.spawn((
+            AudioPlayer(game_audio.bad.clone()),
+            PlaybackSettings::DESPAWN.with_volume(Volume::new(**audio_setting as f32 / 100.)),
+        ));
+
+        return;
+    };
+
+    let Ok(placed_tower) = placed_tower_query.get(selected_tile) else {
+        commands.spawn((
+            AudioPlayer(game_audio.bad.clone()),
+            PlaybackSettings::DESPAWN.with_volume(Volume::new(**audio_setting as f32 / 100.)),
+        ));
+        return;
+    };
+
+    let Ok(mut ammo) = tower_query.get_mut(placed_tower.0) else {
+        commands.spawn((
+            AudioPlayer(game_audio.bad.clone()),
+            PlaybackSettings::DESPAWN.with_volume(Volume::new(**audio_setting as f32 / 100.)),
+        ));
+        return;
+    };
+
+    ammo.current = ammo.max;
+    stats.ammo_delivered += 1;
+
+    commands.spawn((
+        AudioPlayer(game_audio.feed.clone()),
+        PlaybackSettings::DESPAWN.with_volume(Volume::new(**audio_setting as f32 / 100.)),
+    ));
+
+    commands.entity(entity).despawn_recursive();
+}
+
+fn cycle_target_priority(
+    mut commands: Commands,
+    player_query: Query<(&Children, &PlayerInput, &SelectedTile, &SelectedItem), With<Player>>,
+    grabbed_item_query: Query<(), With<GrabbedItem>>,
+    placed_tower_query: Query<&PlacedTower>,
+    mut tower_query: Query<&mut TargetPriority, With<Tower>>,
+    game_audio: Res<Sounds>,
+    audio_setting: Res<SfxSetting>,
+) {
+    let Ok((children, input, selected_tile, selected_item)) = player_query.get_single() else {
+        return;
+    };
+
+    if !input.grab {
+        return;
+    }
+
+    // Only an empty-handed player that isn't about to pick something up
+    if selected_item.0.is_some() || grabbed_item_query.iter_many(children).next().is_some() {
+        return;
+    }
+
+    let Some(placed_tower) = selected_tile
+        .0
+        .and_then(|tile| placed_tower_query.get(tile).ok())
+    else {
+        return;
+    };
+
+    let Ok(mut priority) = tower_query.get_mut(placed_tower.0) else {
+        return;
+    };
+
+    *priority = priority.next();
+
+    commands.spawn((
+        AudioPlayer(game_audio.feed.clone()),
+        PlaybackSettings::DESPAWN.with_volume(Volume::new(**audio_setting as f32 / 100.)),
+    ));
+}
+
+/// An empty-handed player can pay to spawn an item without waiting for the spawner in
+/// front of them.
+fn buy_item(
+    mut commands: Commands,
+    player_query: Query<(&Children, &PlayerInput, &SelectedTile, &SelectedItem), With<Player>>,
+    grabbed_item_query: Query<(), With<GrabbedItem>>,
+    mut item_spawner_query: Query<&mut ItemSpawner>,
+    mut currency: ResMut<Currency>,
+    game_audio: Res<Sounds>,
+    audio_setting: Res<SfxSetting>,
+) {
+    let Ok((children, input, selected_tile, selected_item)) = player_query.get_single() else {
+        return;
+    };
+
+    if !input.grab {
+        return;
+    }
+
+    if selected_item.0.is_some() || grabbed_item_query.iter_many(children).next().is_some() {
+        return;
+    }
+
+    let Some(mut item_spawner) = selected_tile
+        .0
+        .and_then(|tile| item_spawner_query.get_mut(tile).ok())
+    else {
+        return;
+    };
+
+    // The item is already waiting to be picked up
+    if item_spawner.timer.finished() {
+        return;
+    }
+
+    let price = price(item_spawner.item);
+    if currency.0 < price {
+        commands.spawn((
+            AudioPlayer(game_audio.bad.clone()),
+            PlaybackSettings::DESPAWN.with_volume(Volume::new(**audio_setting as f32 / 100.)),
+        ));
+        return;
+    }
+
+    currency.0 -= price;
+    item_spawner.finish_next_tick();
+
+    commands.spawn((
+        AudioPlayer(game_audio.feed.clone()),
+        PlaybackSettings::DESPAWN.with_volume(Volume::new(**audio_setting as f32 / 100.)),
+    ));
+}
+
+fn reset_item_on_grab(mut item_query: Query<&mut Transform, Added<GrabbedItem>>) {
+    for mut transform in item_query.iter_mut() {
+        transform.translation = Vec3::new(0., -0.4, -0.75);
+        transform.rotation = Quat::IDENTITY;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn respawn_history_skips_unsafe_tiles() {
+        let mut history = RespawnHistory::new(UVec2::new(0, 0));
+        for x in 1..=RESPAWN_HISTORY_LEN as u32 {
+            history.push(UVec2::new(x, 0));
+        }
+        // Standing on a tile again makes it the most recent.
+        history.push(UVec2::new(3, 0));
+
+        assert_eq!(history.0.len(), RESPAWN_HISTORY_LEN);
+        assert_eq!(history.latest(|_| true), Some(UVec2::new(3, 0)));
+        assert_eq!(
+            history.latest(|tile| tile.x != 3),
+            Some(UVec2::new(RESPAWN_HISTORY_LEN as u32, 0))
+        );
+        // The oldest tile was forgotten.
+        assert_eq!(history.latest(|tile| tile.x == 0), None);
+    }
+
+    #[test]
+    fn respawn_falls_back_to_a_safe_tile_near_the_start() {
+        let start = UVec2::new(0, 0);
+        let mut history = RespawnHistory::new(UVec2::new(1, 0));
+        history.push(UVec2::new(2, 0));
+
+        // The start is used once nothing in the history is safe.
+        let safe_tiles = [UVec2::new(5, 5), start];
+        assert_eq!(respawn_tile(&history, start, &safe_tiles), start);
+
+        // With towers on the start and every tile in the history, the closest tile
+        // without one is picked instead.
+        let safe_tiles = [UVec2::new(5, 5), UVec2::new(0, 4), UVec2::new(3, 1)];
+        assert_eq!(respawn_tile(&history, start, &safe_tiles), UVec2::new(3, 1));
+    }
+}
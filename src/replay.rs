@@ -0,0 +1,346 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    level::{insert_level, CurrentLevel},
+    loading::{Fonts, Levels},
+    pause::PlayState,
+    player::{Player, PlayerInput, PlayerInputSet},
+    rng::{self, GameRng, GameSeed},
+    settings::DifficultySetting,
+    ui::{OVERLAY, UI_TEXT},
+    waves::GameMode,
+    DespawnOnReset, GameState, RunTick, TickRate,
+};
+
+/// The most recent run is recorded here when it ends, overwriting the previous recording.
+#[cfg(not(target_arch = "wasm32"))]
+pub const REPLAY_FILE: &str = "replay.ron";
+
+const MIN_SPEED: f32 = 0.25;
+const MAX_SPEED: f32 = 8.;
+
+pub struct ReplayPlugin;
+
+/// Everything needed to reproduce a run, given the same level and wave files.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Replay {
+    pub seed: u64,
+    /// Index of the level in `Levels::levels`.
+    pub level: usize,
+    pub difficulty: DifficultySetting,
+    #[serde(default)]
+    pub mode: GameMode,
+    pub tick_rate: f64,
+    /// The player's input on each tick where it changed, counting from the start of the
+    /// run.
+    pub inputs: Vec<(u32, PlayerInput)>,
+}
+
+#[derive(Resource)]
+struct Recording(Replay);
+
+/// Plays back a replay instead of reading the player's input. If this is present when
+/// the main menu is reached, the replay's level starts straight away.
+#[derive(Resource)]
+pub struct Playback {
+    replay: Replay,
+    /// Index of the next entry in `Replay::inputs`.
+    next: usize,
+}
+impl Playback {
+    pub fn new(replay: Replay) -> Self {
+        Self { replay, next: 0 }
+    }
+}
+
+#[derive(Component)]
+struct PlaybackText;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            OnEnter(GameState::Playing),
+            start_recording
+                .after(rng::seed)
+                .run_if(not(resource_exists::<Playback>)),
+        )
+        .add_systems(
+            FixedUpdate,
+            record
+                .after(PlayerInputSet)
+                .run_if(in_state(GameState::Playing))
+                .run_if(resource_exists::<Recording>),
+        );
+
+        // There's nowhere to save a replay to on the web.
+        #[cfg(not(target_arch = "wasm32"))]
+        app.add_systems(OnExit(GameState::Playing), save_recording)
+            .add_systems(Last, save_recording_on_exit);
+
+        app.add_systems(
+            OnEnter(GameState::MainMenu),
+            start_playback.run_if(resource_exists::<Playback>),
+        )
+        .add_systems(
+            OnExit(GameState::MainMenu),
+            insert_level.run_if(resource_exists::<Playback>),
+        )
+        .add_systems(
+            OnEnter(GameState::Playing),
+            (rewind, spawn_playback_text).run_if(resource_exists::<Playback>),
+        )
+        .add_systems(
+            FixedUpdate,
+            play.in_set(PlayerInputSet)
+                .run_if(in_state(GameState::Playing))
+                .run_if(resource_exists::<Playback>),
+        )
+        .add_systems(
+            Update,
+            playback_controls
+                .run_if(in_state(PlayState::Running))
+                .run_if(resource_exists::<Playback>),
+        )
+        .add_systems(OnExit(GameState::Playing), reset_speed);
+    }
+}
+
+impl Replay {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let file = std::fs::File::open(path).map_err(|e| e.to_string())?;
+        ron::de::from_reader(file).map_err(|e| e.to_string())
+    }
+}
+
+fn start_recording(
+    mut commands: Commands,
+    rng: Res<GameRng>,
+    current: Res<CurrentLevel>,
+    difficulty: Res<DifficultySetting>,
+    mode: Res<GameMode>,
+    tick_rate: Res<TickRate>,
+) {
+    commands.insert_resource(Recording(Replay {
+        seed: rng.seed(),
+        level: current.0,
+        difficulty: difficulty.clone(),
+        mode: *mode,
+        tick_rate: tick_rate.0,
+        inputs: vec![],
+    }));
+}
+
+fn record(
+    mut recording: ResMut<Recording>,
+    tick: Res<RunTick>,
+    query: Query<&PlayerInput, With<Player>>,
+) {
+    let Ok(input) = query.get_single() else {
+        return;
+    };
+
+    let inputs = &mut recording.0.inputs;
+    if inputs.last().is_some_and(|(_, last)| last == input) {
+        return;
+    }
+
+    inputs.push((tick.0, *input));
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn save_recording(mut commands: Commands, recording: Option<Res<Recording>>) {
+    let Some(recording) = recording else {
+        return;
+    };
+
+    let file = match std::fs::File::create(REPLAY_FILE) {
+        Ok(f) => f,
+        Err(e) => {
+            warn!("Failed to create replay file: {:?}", e);
+            return;
+        }
+    };
+
+    if let Err(e) = ron::ser::to_writer(file, &recording.0) {
+        warn!("Failed to serialize replay: {:?}", e);
+        return;
+    }
+
+    info!("Saved replay to {}.", REPLAY_FILE);
+
+    commands.remove_resource::<Recording>();
+}
+
+/// Keeps the recording of a run that is quit before it's over.
+#[cfg(not(target_arch = "wasm32"))]
+fn save_recording_on_exit(
+    commands: Commands,
+    recording: Option<Res<Recording>>,
+    exit_events: EventReader<AppExit>,
+) {
+    if exit_events.is_empty() {
+        return;
+    }
+
+    save_recording(commands, recording);
+}
+
+fn start_playback(
+    playback: Res<Playback>,
+    levels: Res<Levels>,
+    mut current: ResMut<CurrentLevel>,
+    mut difficulty: ResMut<DifficultySetting>,
+    mut seed: ResMut<GameSeed>,
+    mut mode: ResMut<GameMode>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let replay = &playback.replay;
+
+    if replay.level >= levels.levels.len() {
+        error!("Replay is for level {}, which doesn't exist.", replay.level);
+        return;
+    }
+
+    current.0 = replay.level;
+    *difficulty = replay.difficulty.clone();
+    seed.0 = Some(replay.seed);
+    *mode = replay.mode;
+
+    next_state.set(GameState::Playing);
+}
+
+fn rewind(mut playback: ResMut<Playback>) {
+    playback.next = 0;
+}
+
+fn play(
+    mut playback: ResMut<Playback>,
+    tick: Res<RunTick>,
+    mut query: Query<&mut PlayerInput, With<Player>>,
+) {
+    let Ok(mut input) = query.get_single_mut() else {
+        return;
+    };
+
+    while let Some(&(input_tick, recorded)) = playback.replay.inputs.get(playback.next) {
+        if input_tick > tick.0 {
+            break;
+        }
+
+        *input = recorded;
+        playback.next += 1;
+    }
+}
+
+fn spawn_playback_text(mut commands: Commands, fonts: Res<Fonts>) {
+    commands
+        .spawn((
+            Name::new("PlaybackContainer"),
+            Node {
+                position_type: PositionType::Absolute,
+                bottom: Val::Px(0.0),
+                left: Val::Px(0.0),
+                padding: UiRect::all(Val::Px(5.)),
+                ..default()
+            },
+            BackgroundColor(OVERLAY.into()),
+            DespawnOnReset,
+        ))
+        .with_child((
+            PlaybackText,
+            Text::new(playback_text(1., false)),
+            TextFont {
+                font: fonts.main.clone(),
+                font_size: 16.,
+                ..default()
+            },
+            TextColor(UI_TEXT.into()),
+        ));
+}
+
+fn playback_text(speed: f32, paused: bool) -> String {
+    if paused {
+        "REPLAY PAUSED  [SPACE] PLAY".to_string()
+    } else {
+        format!("REPLAY {}x  [SPACE] PAUSE  [-/+] SPEED", speed)
+    }
+}
+
+/// Space pauses and resumes, and minus and plus halve and double the speed.
+fn playback_controls(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut time: ResMut<Time<Virtual>>,
+    mut text_query: Query<&mut Text, With<PlaybackText>>,
+) {
+    if !keys.any_just_pressed([KeyCode::Space, KeyCode::Equal, KeyCode::Minus]) {
+        return;
+    }
+
+    if keys.just_pressed(KeyCode::Space) {
+        if time.is_paused() {
+            time.unpause();
+        } else {
+            time.pause();
+        }
+    }
+
+    let speed = time.relative_speed();
+    if keys.just_pressed(KeyCode::Equal) {
+        time.set_relative_speed((speed * 2.).min(MAX_SPEED));
+    }
+    if keys.just_pressed(KeyCode::Minus) {
+        time.set_relative_speed((speed / 2.).max(MIN_SPEED));
+    }
+
+    for mut text in text_query.iter_mut() {
+        text.0 = playback_text(time.relative_speed(), time.is_paused());
+    }
+}
+
+fn reset_speed(mut time: ResMut<Time<Virtual>>) {
+    time.unpause();
+    time.set_relative_speed(1.);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inputs_survive_a_round_trip() {
+        let replay = Replay {
+            seed: 42,
+            level: 1,
+            difficulty: DifficultySetting::Hard,
+            mode: GameMode::Endless,
+            tick_rate: 60.,
+            inputs: vec![
+                (
+                    1,
+                    PlayerInput {
+                        run: Vec2::new(0.1, -1. / 3.),
+                        jump: false,
+                        grab: false,
+                    },
+                ),
+                (
+                    7,
+                    PlayerInput {
+                        run: Vec2::new(0.6, 0.8),
+                        jump: true,
+                        grab: true,
+                    },
+                ),
+            ],
+        };
+
+        let loaded: Replay = ron::de::from_str(&ron::ser::to_string(&replay).unwrap()).unwrap();
+
+        assert_eq!(loaded.seed, replay.seed);
+        assert_eq!(loaded.level, replay.level);
+        assert_eq!(loaded.mode, replay.mode);
+        assert_eq!(loaded.tick_rate, replay.tick_rate);
+        assert_eq!(loaded.inputs, replay.inputs);
+    }
+}
@@ -0,0 +1,46 @@
+use bevy::prelude::*;
+use rand::{rngs::StdRng, thread_rng, Rng, SeedableRng};
+
+use crate::GameState;
+
+pub struct RngPlugin;
+
+/// The source of randomness for everything in a run, so that the run can be reproduced
+/// from its seed.
+#[derive(Resource, Deref, DerefMut)]
+pub struct GameRng {
+    seed: u64,
+    #[deref]
+    rng: StdRng,
+}
+impl GameRng {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+}
+
+/// The seed to use for the next run. A random seed is picked if this is `None`.
+#[derive(Resource, Default)]
+pub struct GameSeed(pub Option<u64>);
+
+impl Plugin for RngPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GameSeed>()
+            .add_systems(OnEnter(GameState::Playing), seed);
+    }
+}
+
+pub fn seed(mut commands: Commands, seed: Res<GameSeed>) {
+    let seed = seed.0.unwrap_or_else(|| thread_rng().gen());
+
+    info!("Starting run with seed {}.", seed);
+
+    commands.insert_resource(GameRng::new(seed));
+}
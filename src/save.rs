@@ -1,4 +1,9 @@
-use crate::settings::{DifficultySetting, MusicSetting, SfxSetting};
+use crate::{
+    level::UnlockedLevels,
+    replay::Playback,
+    score::{BestWaves, HighScores},
+    settings::{DifficultySetting, HealthBarSetting, MusicSetting, SfxSetting},
+};
 
 use bevy::prelude::*;
 use ron::ser::PrettyConfig;
@@ -12,7 +17,8 @@ const LOCAL_STORAGE_KEY: &str = "undefended-save";
 pub struct SavePlugin;
 impl Plugin for SavePlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, save_system);
+        // A replay plays with its own settings, which shouldn't replace the player's.
+        app.add_systems(Update, save_system.run_if(not(resource_exists::<Playback>)));
         app.add_systems(Startup, load_system);
     }
 }
@@ -22,12 +28,24 @@ struct SaveFile {
     sfx: SfxSetting,
     music: MusicSetting,
     difficulty: DifficultySetting,
+    #[serde(default)]
+    health_bars: HealthBarSetting,
+    #[serde(default)]
+    unlocked_levels: UnlockedLevels,
+    #[serde(default)]
+    high_scores: HighScores,
+    #[serde(default)]
+    best_waves: BestWaves,
 }
 
 pub fn load_system(mut commands: Commands) {
     commands.insert_resource(SfxSetting::default());
     commands.insert_resource(MusicSetting::default());
     commands.insert_resource(DifficultySetting::default());
+    commands.insert_resource(HealthBarSetting::default());
+    commands.insert_resource(UnlockedLevels::default());
+    commands.insert_resource(HighScores::default());
+    commands.insert_resource(BestWaves::default());
 
     #[cfg(not(target_arch = "wasm32"))]
     {
@@ -49,6 +67,10 @@ pub fn load_system(mut commands: Commands) {
         commands.insert_resource(save_file.sfx);
         commands.insert_resource(save_file.music);
         commands.insert_resource(save_file.difficulty);
+        commands.insert_resource(save_file.health_bars);
+        commands.insert_resource(save_file.unlocked_levels);
+        commands.insert_resource(save_file.high_scores);
+        commands.insert_resource(save_file.best_waves);
     }
     #[cfg(target_arch = "wasm32")]
     {
@@ -80,6 +102,10 @@ pub fn load_system(mut commands: Commands) {
         commands.insert_resource(save_file.sfx);
         commands.insert_resource(save_file.music);
         commands.insert_resource(save_file.difficulty);
+        commands.insert_resource(save_file.health_bars);
+        commands.insert_resource(save_file.unlocked_levels);
+        commands.insert_resource(save_file.high_scores);
+        commands.insert_resource(save_file.best_waves);
     }
 }
 
@@ -87,12 +113,27 @@ pub fn save_system(
     sfx: Res<SfxSetting>,
     music: Res<MusicSetting>,
     difficulty: Res<DifficultySetting>,
+    health_bars: Res<HealthBarSetting>,
+    unlocked_levels: Res<UnlockedLevels>,
+    high_scores: Res<HighScores>,
+    best_waves: Res<BestWaves>,
 ) {
     let sfx_changed = sfx.is_changed() && !sfx.is_added();
     let music_changed = music.is_changed() && !music.is_added();
     let difficulty_changed = difficulty.is_changed() && !difficulty.is_added();
-
-    if !sfx_changed && !music_changed && !difficulty_changed {
+    let health_bars_changed = health_bars.is_changed() && !health_bars.is_added();
+    let unlocked_levels_changed = unlocked_levels.is_changed() && !unlocked_levels.is_added();
+    let high_scores_changed = high_scores.is_changed() && !high_scores.is_added();
+    let best_waves_changed = best_waves.is_changed() && !best_waves.is_added();
+
+    if !sfx_changed
+        && !music_changed
+        && !difficulty_changed
+        && !health_bars_changed
+        && !unlocked_levels_changed
+        && !high_scores_changed
+        && !best_waves_changed
+    {
         return;
     }
 
@@ -102,6 +143,10 @@ pub fn save_system(
         sfx: sfx.clone(),
         music: music.clone(),
         difficulty: difficulty.clone(),
+        health_bars: *health_bars,
+        unlocked_levels: unlocked_levels.clone(),
+        high_scores: high_scores.clone(),
+        best_waves: best_waves.clone(),
     };
 
     let pretty = PrettyConfig::new();
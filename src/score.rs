@@ -0,0 +1,185 @@
+use bevy::{prelude::*, utils::HashMap};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    level::Level,
+    lives::Lives,
+    replay::Playback,
+    settings::DifficultySetting,
+    stats::{run_secs, RunStats},
+    waves::Waves,
+    GameState, RunTick, TickRate,
+};
+
+/// The number of scores kept for each level and difficulty.
+pub const HIGH_SCORES_LEN: usize = 5;
+
+pub struct ScorePlugin;
+
+/// The best scores for each level, by name, and difficulty, highest first.
+#[derive(Resource, Default, Debug, Serialize, Deserialize, Clone)]
+pub struct HighScores(HashMap<(String, DifficultySetting), Vec<u32>>);
+impl HighScores {
+    pub fn get(&self, level: &str, difficulty: &DifficultySetting) -> &[u32] {
+        self.0
+            .get(&(level.to_string(), difficulty.clone()))
+            .map_or(&[], Vec::as_slice)
+    }
+
+    /// Adds a score to the table, returning its position if it made the table.
+    pub fn insert(
+        &mut self,
+        level: &str,
+        difficulty: &DifficultySetting,
+        score: u32,
+    ) -> Option<usize> {
+        let scores = self
+            .0
+            .entry((level.to_string(), difficulty.clone()))
+            .or_default();
+
+        let rank = scores.partition_point(|s| *s >= score);
+        if rank >= HIGH_SCORES_LEN {
+            return None;
+        }
+
+        scores.insert(rank, score);
+        scores.truncate(HIGH_SCORES_LEN);
+
+        Some(rank)
+    }
+}
+
+/// The furthest wave reached in endless mode on each level, by name, and difficulty.
+#[derive(Resource, Default, Debug, Serialize, Deserialize, Clone)]
+pub struct BestWaves(HashMap<(String, DifficultySetting), usize>);
+impl BestWaves {
+    pub fn get(&self, level: &str, difficulty: &DifficultySetting) -> Option<usize> {
+        self.0
+            .get(&(level.to_string(), difficulty.clone()))
+            .copied()
+    }
+
+    /// Records the wave reached by a run, returning whether it's the furthest yet.
+    pub fn insert(&mut self, level: &str, difficulty: &DifficultySetting, wave: usize) -> bool {
+        let best = self
+            .0
+            .entry((level.to_string(), difficulty.clone()))
+            .or_default();
+
+        if wave <= *best {
+            return false;
+        }
+
+        *best = wave;
+        true
+    }
+}
+
+/// The score of the run that just ended.
+#[derive(Resource, Debug)]
+pub struct RunScore {
+    pub score: u32,
+    /// Position in the high score table, if the score made it. Endless runs aren't
+    /// ranked by score.
+    pub rank: Option<usize>,
+    /// The wave reached, counting from 1, and whether it's the furthest yet, for an
+    /// endless run.
+    pub endless_wave: Option<(usize, bool)>,
+}
+
+impl Plugin for ScorePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(GameState::GameOver), record);
+    }
+}
+
+/// Scores a run. Killing tougher enemies, keeping more lives and surviving for longer
+/// are all worth points, and harder difficulties multiply the total.
+pub fn score(stats: &RunStats, lives: u32, secs: f32, difficulty: &DifficultySetting) -> u32 {
+    let points = stats.killed_hp * 10 + lives * 250 + secs as u32;
+
+    let multiplier = match difficulty {
+        DifficultySetting::Normal => 2,
+        DifficultySetting::Hard => 3,
+        DifficultySetting::Extra => 4,
+    };
+
+    points * multiplier / 2
+}
+
+pub fn record(
+    mut commands: Commands,
+    stats: Res<RunStats>,
+    lives: Res<Lives>,
+    tick: Res<RunTick>,
+    tick_rate: Res<TickRate>,
+    difficulty: Res<DifficultySetting>,
+    level: Res<Level>,
+    waves: Res<Waves>,
+    mut high_scores: ResMut<HighScores>,
+    mut best_waves: ResMut<BestWaves>,
+    playback: Option<Res<Playback>>,
+) {
+    let score = score(&stats, lives.0, run_secs(&tick, &tick_rate), &difficulty);
+
+    // Watching a replay doesn't earn a place in the tables.
+    let recorded = playback.is_none();
+
+    if waves.endless {
+        let wave = waves.current + 1;
+        let best = recorded && best_waves.insert(&level.name, &difficulty, wave);
+
+        commands.insert_resource(RunScore {
+            score,
+            rank: None,
+            endless_wave: Some((wave, best)),
+        });
+        return;
+    }
+
+    let rank = if recorded {
+        high_scores.insert(&level.name, &difficulty, score)
+    } else {
+        None
+    };
+
+    commands.insert_resource(RunScore {
+        score,
+        rank,
+        endless_wave: None,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn high_scores_are_sorted_and_bounded() {
+        let mut high_scores = HighScores::default();
+        let difficulty = DifficultySetting::Normal;
+
+        for score in [30, 10, 50, 20, 40] {
+            assert!(high_scores.insert("Test", &difficulty, score).is_some());
+        }
+
+        assert_eq!(high_scores.insert("Test", &difficulty, 35), Some(2));
+        assert_eq!(high_scores.insert("Test", &difficulty, 5), None);
+        assert_eq!(high_scores.get("Test", &difficulty), &[50, 40, 35, 30, 20]);
+
+        assert!(high_scores.get("Test", &DifficultySetting::Hard).is_empty());
+    }
+
+    #[test]
+    fn best_wave_only_goes_up() {
+        let mut best_waves = BestWaves::default();
+        let difficulty = DifficultySetting::Hard;
+
+        assert!(best_waves.insert("Test", &difficulty, 12));
+        assert!(!best_waves.insert("Test", &difficulty, 9));
+        assert!(!best_waves.insert("Test", &difficulty, 12));
+        assert_eq!(best_waves.get("Test", &difficulty), Some(12));
+        assert_eq!(best_waves.get("Test", &DifficultySetting::Normal), None);
+    }
+}
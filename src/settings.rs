@@ -10,6 +10,11 @@ impl Default for MusicSetting {
         Self(100)
     }
 }
+impl MusicSetting {
+    pub fn label(&self) -> String {
+        format!("MUSIC {}%", self.0)
+    }
+}
 
 #[derive(Resource, Deref, DerefMut, Debug, Serialize, Deserialize, Clone)]
 pub struct SfxSetting(u8);
@@ -18,7 +23,22 @@ impl Default for SfxSetting {
         Self(100)
     }
 }
-#[derive(Resource, Debug, Default, Serialize, Deserialize, Clone)]
+impl SfxSetting {
+    pub fn label(&self) -> String {
+        format!("SFX {}%", self.0)
+    }
+}
+
+/// Turns a volume down by 10%, wrapping back around to 100% after muting it.
+pub fn cycle_volume(volume: u8) -> u8 {
+    if volume == 0 {
+        100
+    } else {
+        volume.saturating_sub(10)
+    }
+}
+
+#[derive(Resource, Debug, Default, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
 pub enum DifficultySetting {
     #[default]
     Normal,
@@ -47,3 +67,48 @@ impl Display for DifficultySetting {
         )
     }
 }
+
+/// When to show health bars over enemies.
+#[derive(Resource, Debug, Default, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum HealthBarSetting {
+    Always,
+    /// Only once an enemy has lost some hit points.
+    #[default]
+    Damaged,
+    Never,
+}
+impl HealthBarSetting {
+    pub fn next(&self) -> Self {
+        match self {
+            Self::Always => Self::Damaged,
+            Self::Damaged => Self::Never,
+            Self::Never => Self::Always,
+        }
+    }
+
+    pub fn label(&self) -> String {
+        format!("HP {}", self)
+    }
+}
+impl Display for HealthBarSetting {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Always => "ALWAYS",
+                Self::Damaged => "DAMAGED",
+                Self::Never => "NEVER",
+            }
+        )
+    }
+}
+
+// Settings can be changed from both the main menu and the pause menu, and these mark the
+// text of the buttons that show them.
+#[derive(Component)]
+pub struct SfxSettingButtonText;
+#[derive(Component)]
+pub struct MusicSettingButtonText;
+#[derive(Component)]
+pub struct HealthBarSettingButtonText;
@@ -14,7 +14,7 @@ use bevy::{
     sprite::{Material2d, Material2dKey, Material2dPlugin},
 };
 
-use crate::{GameState, Player};
+use crate::{player::Player, GameState};
 
 // This attribute only exists to ensure that our starfield mesh ends up in its own
 // unique vertex buffer, which allows the built-in `FULLSCREEN_SHADER_HANDLE` vertex
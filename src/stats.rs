@@ -0,0 +1,96 @@
+use bevy::prelude::*;
+
+use crate::{
+    damage::{self, EnemyDamaged, EnemyKilled},
+    tower::TowerKind,
+    GameState, RunTick, TickRate,
+};
+
+pub struct StatsPlugin;
+
+/// What happened during the current run, or the last one once it's over.
+#[derive(Resource, Default, Clone, Debug)]
+pub struct RunStats {
+    pub kills: u32,
+    /// The total maximum hit points of the enemies killed.
+    pub killed_hp: u32,
+    /// Indexed by wave, up to the last wave that an enemy was killed or leaked in.
+    pub waves: Vec<WaveStats>,
+    pub lasers_fired: u32,
+    /// Lasers that reached their target, rather than losing it on the way.
+    pub lasers_hit: u32,
+    /// Laser ammo fed to towers.
+    pub ammo_delivered: u32,
+    /// Times that the player fell into the lava.
+    pub falls: u32,
+    /// Every tower built, in the order they were built.
+    pub towers: Vec<BuiltTower>,
+}
+impl RunStats {
+    pub fn wave_mut(&mut self, wave: usize) -> &mut WaveStats {
+        if self.waves.len() <= wave {
+            self.waves.resize(wave + 1, WaveStats::default());
+        }
+
+        &mut self.waves[wave]
+    }
+}
+
+#[derive(Default, Clone, Debug)]
+pub struct WaveStats {
+    pub killed: u32,
+    /// Enemies that reached the goal.
+    pub leaked: u32,
+}
+
+#[derive(Clone, Debug)]
+pub struct BuiltTower {
+    pub kind: TowerKind,
+    /// Hit points taken from enemies.
+    pub damage: u32,
+}
+
+/// Index of a tower in `RunStats::towers`.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct BuildOrder(pub usize);
+
+impl Plugin for StatsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<RunStats>()
+            .add_systems(OnEnter(GameState::Playing), reset)
+            .add_systems(
+                FixedUpdate,
+                (record_damage, record_kills)
+                    .run_if(in_state(GameState::Playing))
+                    .after(damage::resolve),
+            );
+    }
+}
+
+/// Seconds of gameplay since the current run started.
+pub fn run_secs(tick: &RunTick, tick_rate: &TickRate) -> f32 {
+    (tick.0 as f64 / tick_rate.0) as f32
+}
+
+fn record_damage(mut events: EventReader<EnemyDamaged>, mut stats: ResMut<RunStats>) {
+    for event in events.read() {
+        if let Some(tower) = event
+            .source
+            .and_then(|source| stats.towers.get_mut(source.0))
+        {
+            tower.damage += event.amount;
+        }
+    }
+}
+
+fn record_kills(mut events: EventReader<EnemyKilled>, mut stats: ResMut<RunStats>) {
+    for event in events.read() {
+        stats.kills += 1;
+        stats.killed_hp += event.max_hp;
+        stats.wave_mut(event.wave).killed += 1;
+    }
+}
+
+fn reset(mut commands: Commands) {
+    commands.insert_resource(RunStats::default());
+}
@@ -0,0 +1,315 @@
+use bevy::prelude::*;
+
+use crate::{
+    damage::{self, Damage, DamageKind},
+    enemy::Enemy,
+    stats::BuildOrder,
+    GameState,
+};
+
+/// The most instances of one kind of effect that an enemy can have at once. Applying
+/// another replaces the one with the least time left.
+pub const MAX_STACKS: usize = 3;
+
+/// Seconds between each tick of damage from a burn.
+const BURN_INTERVAL: f32 = 1.;
+
+pub struct StatusPlugin;
+
+/// Models for status effects, which aren't needed to simulate the game.
+pub struct StatusPresentationPlugin;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EffectKind {
+    /// Multiplies the enemy's speed.
+    Slow(f32),
+    /// Deals fire damage every `BURN_INTERVAL` seconds.
+    Burn(u32),
+    /// Stops the enemy in its tracks.
+    Stun,
+    /// Armor stops absorbing hits, and hits deal half as much damage again.
+    ArmorBreak,
+}
+impl EffectKind {
+    fn same_kind(&self, other: &Self) -> bool {
+        std::mem::discriminant(self) == std::mem::discriminant(other)
+    }
+}
+
+/// An effect that a tower's shots apply to the enemies they hit.
+#[derive(Clone, Debug)]
+pub struct StatusEffect {
+    pub kind: EffectKind,
+    /// Seconds that the effect lasts.
+    pub duration: f32,
+}
+
+#[derive(Debug)]
+pub struct ActiveEffect {
+    pub kind: EffectKind,
+    pub timer: Timer,
+    /// The tower that applied the effect, credited with any damage it deals.
+    pub source: Option<BuildOrder>,
+}
+
+/// The effects that an enemy is currently under.
+#[derive(Component, Default, Debug)]
+pub struct StatusEffects(pub Vec<ActiveEffect>);
+impl StatusEffects {
+    pub fn apply(&mut self, effect: &StatusEffect, source: Option<BuildOrder>) {
+        let active = ActiveEffect {
+            kind: effect.kind,
+            timer: Timer::from_seconds(effect.duration, TimerMode::Once),
+            source,
+        };
+
+        let stacks = self.0.iter().filter(|e| e.kind.same_kind(&effect.kind));
+        if stacks.clone().count() < MAX_STACKS {
+            self.0.push(active);
+            return;
+        }
+
+        if let Some(weakest) = self
+            .0
+            .iter_mut()
+            .filter(|e| e.kind.same_kind(&effect.kind))
+            .min_by(|a, b| a.timer.remaining().cmp(&b.timer.remaining()))
+        {
+            *weakest = active;
+        }
+    }
+
+    /// Multiplier applied to the enemy's speed.
+    pub fn speed_multiplier(&self) -> f32 {
+        self.0
+            .iter()
+            .map(|effect| match effect.kind {
+                EffectKind::Slow(multiplier) => multiplier,
+                EffectKind::Stun => 0.,
+                _ => 1.,
+            })
+            .product()
+    }
+
+    /// Multiplier applied to the damage that the enemy takes.
+    pub fn damage_taken_multiplier(&self) -> f32 {
+        let breaks = self
+            .0
+            .iter()
+            .filter(|effect| effect.kind == EffectKind::ArmorBreak)
+            .count();
+
+        1. + 0.5 * breaks as f32
+    }
+
+    pub fn armor_broken(&self) -> bool {
+        self.0
+            .iter()
+            .any(|effect| effect.kind == EffectKind::ArmorBreak)
+    }
+}
+
+#[derive(Component)]
+pub struct EffectIndicator;
+
+/// The kinds of effect that an enemy's indicators were last spawned for.
+#[derive(Component, Default)]
+struct ShownEffects(Vec<EffectKind>);
+
+#[derive(Resource)]
+struct IndicatorAssets {
+    mesh: Handle<Mesh>,
+    slow: Handle<StandardMaterial>,
+    burn: Handle<StandardMaterial>,
+    stun: Handle<StandardMaterial>,
+    armor_break: Handle<StandardMaterial>,
+}
+impl IndicatorAssets {
+    fn material(&self, kind: &EffectKind) -> Handle<StandardMaterial> {
+        match kind {
+            EffectKind::Slow(_) => self.slow.clone(),
+            EffectKind::Burn(_) => self.burn.clone(),
+            EffectKind::Stun => self.stun.clone(),
+            EffectKind::ArmorBreak => self.armor_break.clone(),
+        }
+    }
+}
+impl FromWorld for IndicatorAssets {
+    fn from_world(world: &mut World) -> Self {
+        let mesh = world.resource_mut::<Assets<Mesh>>().add(Sphere::new(0.12));
+
+        let mut materials = world.resource_mut::<Assets<StandardMaterial>>();
+        let mut material = |color: Color| {
+            materials.add(StandardMaterial {
+                base_color: color,
+                unlit: true,
+                ..default()
+            })
+        };
+
+        Self {
+            mesh,
+            slow: material(Color::srgb(0.3, 0.6, 1.0)),
+            burn: material(Color::srgb(1.0, 0.4, 0.1)),
+            stun: material(Color::srgb(1.0, 1.0, 0.3)),
+            armor_break: material(Color::srgb(0.6, 0.6, 0.6)),
+        }
+    }
+}
+
+impl Plugin for StatusPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            FixedUpdate,
+            tick_effects
+                .run_if(in_state(GameState::Playing))
+                .before(damage::resolve),
+        );
+    }
+}
+
+impl Plugin for StatusPresentationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<IndicatorAssets>()
+            .add_systems(Update, indicators.run_if(in_state(GameState::Playing)));
+    }
+}
+
+/// Deals damage from burns and removes effects that have run out.
+fn tick_effects(
+    mut query: Query<(Entity, &mut StatusEffects), With<Enemy>>,
+    time: Res<Time>,
+    mut damage_events: EventWriter<Damage>,
+) {
+    for (entity, mut effects) in query.iter_mut() {
+        if effects.0.is_empty() {
+            continue;
+        }
+
+        for effect in effects.0.iter_mut() {
+            let before = effect.timer.elapsed_secs();
+            effect.timer.tick(time.delta());
+
+            let EffectKind::Burn(amount) = effect.kind else {
+                continue;
+            };
+
+            let ticks = (effect.timer.elapsed_secs() / BURN_INTERVAL) as u32
+                - (before / BURN_INTERVAL) as u32;
+            for _ in 0..ticks {
+                damage_events.send(Damage {
+                    target: entity,
+                    amount,
+                    kind: DamageKind::Fire,
+                    source: effect.source,
+                    crit: false,
+                    effects: vec![],
+                });
+            }
+        }
+
+        effects.0.retain(|effect| !effect.timer.finished());
+    }
+}
+
+/// Shows a small orb over an enemy for each kind of effect that it's under.
+fn indicators(
+    mut commands: Commands,
+    mut query: Query<
+        (
+            Entity,
+            &StatusEffects,
+            Option<&mut ShownEffects>,
+            Option<&Children>,
+        ),
+        Changed<StatusEffects>,
+    >,
+    indicator_query: Query<(), With<EffectIndicator>>,
+    assets: Res<IndicatorAssets>,
+) {
+    for (entity, effects, shown, children) in query.iter_mut() {
+        let mut kinds: Vec<EffectKind> = vec![];
+        for effect in &effects.0 {
+            if !kinds.iter().any(|kind| kind.same_kind(&effect.kind)) {
+                kinds.push(effect.kind);
+            }
+        }
+
+        // Effects are ticked every frame, but the orbs only need replacing when an
+        // effect starts or runs out.
+        let unchanged = shown.as_ref().is_some_and(|shown| {
+            shown.0.len() == kinds.len() && shown.0.iter().zip(&kinds).all(|(a, b)| a.same_kind(b))
+        });
+        if unchanged {
+            continue;
+        }
+
+        for child in children.into_iter().flatten() {
+            if indicator_query.contains(*child) {
+                commands.entity(*child).despawn_recursive();
+            }
+        }
+
+        let offset = (kinds.len() as f32 - 1.) / 2.;
+        commands.entity(entity).with_children(|parent| {
+            for (i, kind) in kinds.iter().enumerate() {
+                parent.spawn((
+                    EffectIndicator,
+                    Mesh3d(assets.mesh.clone()),
+                    MeshMaterial3d(assets.material(kind)),
+                    Transform::from_xyz((i as f32 - offset) * 0.3, 0.9, 0.),
+                ));
+            }
+        });
+
+        match shown {
+            Some(mut shown) => shown.0 = kinds,
+            None => {
+                commands.entity(entity).insert(ShownEffects(kinds));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn effect(kind: EffectKind, duration: f32) -> StatusEffect {
+        StatusEffect { kind, duration }
+    }
+
+    #[test]
+    fn stacks_are_capped_and_refresh_the_oldest() {
+        let mut effects = StatusEffects::default();
+
+        for duration in [3., 1., 2.] {
+            effects.apply(&effect(EffectKind::Slow(0.5), duration), None);
+        }
+        assert!((effects.speed_multiplier() - 0.125).abs() < 1e-6);
+
+        // A fourth stack replaces the one with the least time left.
+        effects.apply(&effect(EffectKind::Slow(0.8), 4.), None);
+        assert_eq!(effects.0.len(), MAX_STACKS);
+        assert!((effects.speed_multiplier() - 0.2).abs() < 1e-6);
+        assert!(effects
+            .0
+            .iter()
+            .all(|effect| effect.timer.duration().as_secs_f32() != 1.));
+    }
+
+    #[test]
+    fn stun_and_armor_break() {
+        let mut effects = StatusEffects::default();
+        assert!(!effects.armor_broken());
+        assert_eq!(effects.damage_taken_multiplier(), 1.);
+
+        effects.apply(&effect(EffectKind::ArmorBreak, 1.), None);
+        effects.apply(&effect(EffectKind::ArmorBreak, 1.), None);
+        assert!(effects.armor_broken());
+        assert_eq!(effects.damage_taken_multiplier(), 2.);
+
+        effects.apply(&effect(EffectKind::Stun, 1.), None);
+        assert_eq!(effects.speed_multiplier(), 0.);
+    }
+}
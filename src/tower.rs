@@ -1,3 +1,5 @@
+use std::{fmt::Display, time::Duration};
+
 use bevy::audio::Volume;
 use bevy::math::Vec3Swizzles;
 use bevy::{prelude::*, utils::HashSet};
@@ -5,11 +7,18 @@ use bevy_mod_outline::{AsyncSceneInheritOutline, OutlineVolume};
 use bevy_rapier3d::prelude::*;
 use bevy_scene_hook::{HookedSceneBundle, SceneHook};
 use bevy_two_entities::tuple::TupleQueryExt;
+use rand::Rng;
+use serde::Deserialize;
 
-use crate::enemy::{HitPoints, PathIndex};
+use crate::damage::{self, Damage, DamageKind};
+use crate::enemy::{HitPoints, PathProgress};
+use crate::level::Level;
 use crate::loading::Sounds;
-use crate::map::{PlacedTower, TilePos, PATH};
+use crate::map::{PlacedTower, TilePos};
+use crate::rng::GameRng;
 use crate::settings::SfxSetting;
+use crate::stats::{BuildOrder, BuiltTower, RunStats};
+use crate::status::{EffectKind, StatusEffect};
 use crate::DespawnOnReset;
 use crate::{enemy::Enemy, loading::Models, map::map_to_world, GameState};
 
@@ -19,6 +28,11 @@ pub struct Tower;
 #[derive(Component)]
 pub struct TowerHead;
 
+/// Where the head sits on the tower model, relative to the tower.
+const HEAD_OFFSET: Vec3 = Vec3::new(0., 1.5, 0.);
+/// Where lasers are fired from, relative to a tower's head.
+const MUZZLE_OFFSET: Vec3 = Vec3::new(0., -0.2, 0.8);
+
 #[derive(Component, Debug)]
 pub struct Target(pub Option<Entity>);
 
@@ -26,7 +40,201 @@ pub struct Target(pub Option<Entity>);
 pub struct InRange(pub HashSet<Entity>);
 
 #[derive(Event)]
-pub struct SpawnTowerEvent(pub Entity);
+pub struct SpawnTowerEvent(pub Entity, pub TowerKind);
+
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+pub enum TowerKind {
+    /// The original all-rounder.
+    Laser,
+    /// Rapid-fire, but short ranged and hungry for ammo.
+    Gun,
+    /// Slow, long ranged and hard-hitting. Its shots briefly stun and break armor.
+    Sniper,
+    /// Damages every enemy near its target, and sets them burning.
+    Splash,
+    /// Slows its target down instead of damaging it.
+    Slow,
+}
+impl TowerKind {
+    pub fn stats(&self) -> TowerStats {
+        match self {
+            Self::Laser => TowerStats {
+                damage: 1,
+                damage_kind: DamageKind::Energy,
+                crit_chance: 0.,
+                range: 4.,
+                cooldown: 2.5,
+                max_ammo: 20,
+                projectile_speed: 8.,
+                splash_radius: None,
+                effects: vec![],
+            },
+            Self::Gun => TowerStats {
+                damage: 1,
+                damage_kind: DamageKind::Kinetic,
+                crit_chance: 0.1,
+                range: 3.,
+                cooldown: 0.75,
+                max_ammo: 40,
+                projectile_speed: 12.,
+                splash_radius: None,
+                effects: vec![],
+            },
+            Self::Sniper => TowerStats {
+                damage: 5,
+                damage_kind: DamageKind::Kinetic,
+                crit_chance: 0.25,
+                range: 8.,
+                cooldown: 5.,
+                max_ammo: 8,
+                projectile_speed: 24.,
+                splash_radius: None,
+                effects: vec![
+                    StatusEffect {
+                        kind: EffectKind::Stun,
+                        duration: 0.5,
+                    },
+                    StatusEffect {
+                        kind: EffectKind::ArmorBreak,
+                        duration: 3.,
+                    },
+                ],
+            },
+            Self::Splash => TowerStats {
+                damage: 1,
+                damage_kind: DamageKind::Fire,
+                crit_chance: 0.,
+                range: 4.,
+                cooldown: 3.,
+                max_ammo: 15,
+                projectile_speed: 6.,
+                splash_radius: Some(1.5),
+                effects: vec![StatusEffect {
+                    kind: EffectKind::Burn(1),
+                    duration: 2.,
+                }],
+            },
+            Self::Slow => TowerStats {
+                damage: 0,
+                damage_kind: DamageKind::Energy,
+                crit_chance: 0.,
+                range: 4.,
+                cooldown: 2.,
+                max_ammo: 20,
+                projectile_speed: 8.,
+                splash_radius: None,
+                effects: vec![StatusEffect {
+                    kind: EffectKind::Slow(0.5),
+                    duration: 2.,
+                }],
+            },
+        }
+    }
+}
+impl Display for TowerKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Laser => "Laser",
+                Self::Gun => "Gun",
+                Self::Sniper => "Sniper",
+                Self::Splash => "Splash",
+                Self::Slow => "Slow",
+            }
+        )
+    }
+}
+
+/// Which of the enemies in range a tower prefers to shoot at.
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TargetPriority {
+    /// The enemy furthest along the path.
+    #[default]
+    First,
+    /// The enemy least far along the path.
+    Last,
+    /// The enemy with the most hit points.
+    Strongest,
+    /// The enemy with the fewest hit points.
+    Weakest,
+    /// The enemy closest to the tower.
+    Closest,
+}
+impl TargetPriority {
+    /// Returns the priority that follows this one when the player cycles through them.
+    pub fn next(&self) -> Self {
+        match self {
+            Self::First => Self::Last,
+            Self::Last => Self::Strongest,
+            Self::Strongest => Self::Weakest,
+            Self::Weakest => Self::Closest,
+            Self::Closest => Self::First,
+        }
+    }
+}
+impl Display for TargetPriority {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::First => "FIRST",
+                Self::Last => "LAST",
+                Self::Strongest => "STRONG",
+                Self::Weakest => "WEAK",
+                Self::Closest => "CLOSE",
+            }
+        )
+    }
+}
+
+#[derive(Event)]
+pub struct UpgradeTowerEvent(pub Entity);
+
+pub const MAX_TOWER_LEVEL: u32 = 3;
+
+#[derive(Component)]
+pub struct TowerLevel(pub u32);
+impl TowerLevel {
+    pub fn can_upgrade(&self) -> bool {
+        self.0 < MAX_TOWER_LEVEL
+    }
+}
+
+#[derive(Component, Clone, Debug)]
+pub struct TowerStats {
+    /// Damage dealt by each shot.
+    pub damage: u32,
+    pub damage_kind: DamageKind,
+    /// Chance of each shot being a critical hit, from 0 to 1.
+    pub crit_chance: f32,
+    /// Radius of the area in which the tower can target enemies.
+    pub range: f32,
+    /// Seconds between shots.
+    pub cooldown: f32,
+    pub max_ammo: u32,
+    /// Speed of the tower's projectiles, in world units per second.
+    pub projectile_speed: f32,
+    /// If set, shots damage every enemy within this distance of their target.
+    pub splash_radius: Option<f32>,
+    /// Status effects that shots apply to the enemies they hit.
+    pub effects: Vec<StatusEffect>,
+}
+
+impl TowerStats {
+    /// Returns the stats for the next tower level.
+    pub fn upgraded(&self) -> Self {
+        Self {
+            damage: if self.damage > 0 { self.damage + 1 } else { 0 },
+            range: self.range * 1.25,
+            cooldown: self.cooldown * 0.8,
+            max_ammo: self.max_ammo + self.max_ammo / 2,
+            ..self.clone()
+        }
+    }
+}
 
 #[derive(Component)]
 pub struct RangeSensor;
@@ -34,6 +242,19 @@ pub struct RangeSensor;
 #[derive(Component)]
 struct Laser;
 
+/// The attack carried by a `Laser`, copied from the stats of the tower that fired it.
+#[derive(Component)]
+struct Projectile {
+    /// The tower that fired it.
+    tower: BuildOrder,
+    damage: u32,
+    damage_kind: DamageKind,
+    crit: bool,
+    speed: f32,
+    splash_radius: Option<f32>,
+    effects: Vec<StatusEffect>,
+}
+
 #[derive(Component)]
 struct Cooldown(Timer);
 
@@ -68,23 +289,43 @@ pub struct TowerPlugin;
 impl Plugin for TowerPlugin {
     fn build(&self, app: &mut App) {
         app.add_event::<SpawnTowerEvent>()
-            .init_resource::<LaserMaterial>()
-            .add_systems(Update, spawn.run_if(in_state(GameState::Playing)))
-            .add_systems(Update, ranging.run_if(in_state(GameState::Playing)))
+            .add_event::<UpgradeTowerEvent>()
+            .add_systems(FixedUpdate, spawn.run_if(in_state(GameState::Playing)))
+            .add_systems(FixedUpdate, upgrade.run_if(in_state(GameState::Playing)))
+            .add_systems(FixedUpdate, ranging.run_if(in_state(GameState::Playing)))
             .add_systems(
-                Update,
+                FixedUpdate,
                 targeting
                     .run_if(in_state(GameState::Playing))
                     .after(ranging),
             )
             .add_systems(
-                Update,
+                FixedUpdate,
                 shooting
                     .run_if(in_state(GameState::Playing))
                     .after(targeting),
             )
+            .add_systems(
+                FixedUpdate,
+                laser_movement
+                    .run_if(in_state(GameState::Playing))
+                    .before(damage::resolve),
+            );
+    }
+}
+
+/// Models, animation and sounds for towers, which aren't needed to simulate the game.
+pub struct TowerPresentationPlugin;
+
+impl Plugin for TowerPresentationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LaserMaterial>()
+            .add_systems(Update, spawn_model.run_if(in_state(GameState::Playing)))
+            .add_systems(
+                Update,
+                spawn_laser_model.run_if(in_state(GameState::Playing)),
+            )
             .add_systems(Update, movement.run_if(in_state(GameState::Playing)))
-            .add_systems(Update, laser_movement.run_if(in_state(GameState::Playing)))
             .add_systems(Update, build_sound.run_if(in_state(GameState::Playing)))
             .add_systems(Update, laser_sound.run_if(in_state(GameState::Playing)));
     }
@@ -106,14 +347,11 @@ fn movement(
             continue;
         };
 
-        let diff_xz = (transform.translation - target_transform.translation).xz();
+        let aim = aim(transform.translation, target_transform.translation);
 
         for descendant in children_query.iter_descendants(entity) {
             if let Ok(mut head) = tower_head_query.get_mut(descendant) {
-                head.rotation = head.rotation.slerp(
-                    Quat::from_rotation_y(diff_xz.angle_to(-Vec2::Y)),
-                    time.delta_secs() * 10.,
-                );
+                head.rotation = head.rotation.slerp(aim, time.delta_secs() * 10.);
 
                 break;
             }
@@ -121,6 +359,13 @@ fn movement(
     }
 }
 
+/// The rotation of a tower's head when it's facing its target.
+fn aim(tower: Vec3, target: Vec3) -> Quat {
+    let diff_xz = (tower - target).xz();
+
+    Quat::from_rotation_y(diff_xz.angle_to(-Vec2::Y))
+}
+
 fn ranging(
     mut collision_events: EventReader<CollisionEvent>,
     range_sensor_query: Query<&Parent, With<RangeSensor>>,
@@ -154,39 +399,31 @@ fn ranging(
 }
 
 fn targeting(
-    mut tower_query: Query<(&mut Target, &InRange), With<Tower>>,
-    enemy_query: Query<(Entity, &Transform, &PathIndex), With<Enemy>>,
+    mut tower_query: Query<(&mut Target, &InRange, &TargetPriority, &Transform), With<Tower>>,
+    enemy_query: Query<(Entity, &Transform, &PathProgress, &HitPoints), With<Enemy>>,
 ) {
-    for (mut target, in_range) in tower_query.iter_mut() {
-        // Don't pick a new target if we already have one and
-        // it is a valid enemy reference and the enemy is still
-        // in range.
-        if target
-            .0
-            .filter(|t| in_range.0.contains(t))
-            .filter(|t| enemy_query.get(*t).is_ok())
-            .is_some()
-        {
-            continue;
-        }
-
-        // TODO we should sort by path index before distance to destination
-        let mut enemies: Vec<_> = enemy_query
+    for (mut target, in_range, priority, tower_transform) in tower_query.iter_mut() {
+        // Enemies overtake each other and move in and out of range, so the best
+        // target is picked again every frame.
+        target.0 = enemy_query
             .iter_many(&in_range.0)
-            .map(|(entity, transform, path_index)| {
-                let dist =
-                    (transform.translation - map_to_world(PATH[path_index.0])).length_squared();
-                (entity, dist)
-            })
-            .collect();
-
-        enemies.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+            .map(|(entity, transform, progress, hp)| {
+                let progress = progress.travelled;
+                let score = match priority {
+                    TargetPriority::First => progress,
+                    TargetPriority::Last => -progress,
+                    TargetPriority::Strongest => hp.current as f32,
+                    TargetPriority::Weakest => -(hp.current as f32),
+                    TargetPriority::Closest => -tower_transform
+                        .translation
+                        .distance_squared(transform.translation),
+                };
 
-        if let Some((entity, _)) = enemies.first() {
-            target.0 = Some(*entity);
-        } else {
-            target.0 = None;
-        }
+                (entity, score, progress)
+            })
+            // Ties are broken by progress along the path.
+            .max_by(|a, b| a.1.total_cmp(&b.1).then(a.2.total_cmp(&b.2)))
+            .map(|(entity, _, _)| entity);
     }
 }
 
@@ -194,52 +431,50 @@ fn spawn(
     mut commands: Commands,
     mut events: EventReader<SpawnTowerEvent>,
     tile_pos_query: Query<&TilePos>,
-    models: Res<Models>,
+    level: Res<Level>,
+    mut run_stats: ResMut<RunStats>,
 ) {
     for event in events.read() {
         let Ok(tile_pos) = tile_pos_query.get(event.0) else {
             continue;
         };
 
+        let stats = event.1.stats();
+
+        let build_order = BuildOrder(run_stats.towers.len());
+        run_stats.towers.push(BuiltTower {
+            kind: event.1,
+            damage: 0,
+        });
+
         let entity = commands
             .spawn((
                 Tower,
                 Name::new("Tower"),
-                HookedSceneBundle {
-                    scene: SceneRoot(models.tower_base.clone()),
-                    hook: SceneHook::new(|entity, cmds| {
-                        match entity.get::<Name>().map(|t| t.as_str()) {
-                            Some("HeadMesh") => {
-                                cmds.insert(TowerHead);
-                                cmds
-                            }
-                            _ => cmds,
-                        };
-                    }),
-                },
-                Transform::from_translation(map_to_world(tile_pos.0) + Vec3::Y * 0.75),
+                Transform::from_translation(map_to_world(&level, tile_pos.0) + Vec3::Y * 0.75),
                 Target(None),
                 InRange::default(),
-                Cooldown(Timer::from_seconds(2.5, TimerMode::Repeating)),
+                Cooldown(Timer::from_seconds(stats.cooldown, TimerMode::Repeating)),
                 TilePos(tile_pos.0),
-                Ammo::new(20),
+                Ammo::new(stats.max_ammo),
                 RigidBody::Fixed,
                 Collider::cuboid(1.0, 3.0, 1.0),
                 ActiveEvents::COLLISION_EVENTS,
-                OutlineVolume {
-                    width: 3.0,
-                    colour: Color::hsla(160., 0.9, 0.5, 1.0),
-                    visible: true,
-                },
-                AsyncSceneInheritOutline::default(),
                 DespawnOnReset,
             ))
+            .insert((
+                event.1,
+                stats.clone(),
+                TowerLevel(1),
+                TargetPriority::default(),
+                build_order,
+            ))
             .with_children(|parent| {
                 parent.spawn((
                     RangeSensor,
                     Transform::default(),
                     Visibility::default(),
-                    Collider::ball(4.),
+                    Collider::ball(stats.range),
                     Sensor,
                     ActiveCollisionTypes::STATIC_STATIC,
                     ActiveEvents::COLLISION_EVENTS,
@@ -251,13 +486,82 @@ fn spawn(
     }
 }
 
+fn spawn_model(mut commands: Commands, query: Query<Entity, Added<Tower>>, models: Res<Models>) {
+    for entity in query.iter() {
+        commands.entity(entity).insert((
+            HookedSceneBundle {
+                scene: SceneRoot(models.tower_base.clone()),
+                hook: SceneHook::new(|entity, cmds| {
+                    match entity.get::<Name>().map(|t| t.as_str()) {
+                        Some("HeadMesh") => {
+                            cmds.insert(TowerHead);
+                            cmds
+                        }
+                        _ => cmds,
+                    };
+                }),
+            },
+            OutlineVolume {
+                width: 3.0,
+                colour: Color::hsla(160., 0.9, 0.5, 1.0),
+                visible: true,
+            },
+            AsyncSceneInheritOutline::default(),
+        ));
+    }
+}
+
+fn upgrade(
+    mut events: EventReader<UpgradeTowerEvent>,
+    mut tower_query: Query<(
+        &mut TowerLevel,
+        &mut TowerStats,
+        &mut Cooldown,
+        &mut Ammo,
+        &Children,
+    )>,
+    mut range_sensor_query: Query<&mut Collider, With<RangeSensor>>,
+) {
+    for event in events.read() {
+        let Ok((mut level, mut stats, mut cooldown, mut ammo, children)) =
+            tower_query.get_mut(event.0)
+        else {
+            continue;
+        };
+
+        if !level.can_upgrade() {
+            continue;
+        }
+
+        level.0 += 1;
+        *stats = stats.upgraded();
+
+        cooldown
+            .0
+            .set_duration(Duration::from_secs_f32(stats.cooldown));
+        ammo.max = stats.max_ammo;
+        ammo.current = ammo.max;
+
+        for child in children {
+            if let Ok(mut collider) = range_sensor_query.get_mut(*child) {
+                *collider = Collider::ball(stats.range);
+            }
+        }
+    }
+}
+
 fn build_sound(
     mut commands: Commands,
     mut events: EventReader<SpawnTowerEvent>,
+    mut upgrade_events: EventReader<UpgradeTowerEvent>,
     game_audio: Res<Sounds>,
     audio_setting: Res<SfxSetting>,
 ) {
-    if events.read().count() == 0 {
+    // Both readers are drained every frame, so that events aren't heard again later.
+    let built = events.read().count();
+    let upgraded = upgrade_events.read().count();
+
+    if built == 0 && upgraded == 0 {
         return;
     }
 
@@ -269,82 +573,132 @@ fn build_sound(
 
 fn shooting(
     mut commands: Commands,
-    mut tower_query: Query<(Entity, &mut Cooldown, &mut Ammo, &Target), With<Tower>>,
-    tower_head_query: Query<&GlobalTransform, With<TowerHead>>,
-    mut meshes: ResMut<Assets<Mesh>>,
-    material: Res<LaserMaterial>,
+    mut tower_query: Query<
+        (
+            &mut Cooldown,
+            &mut Ammo,
+            &Target,
+            &TowerStats,
+            &BuildOrder,
+            &Transform,
+        ),
+        With<Tower>,
+    >,
+    enemy_query: Query<&Transform, With<Enemy>>,
     time: Res<Time>,
-    children_query: Query<&Children>,
+    mut run_stats: ResMut<RunStats>,
+    mut rng: ResMut<GameRng>,
 ) {
-    let offset = Vec3::new(0., -0.2, 0.8);
-
-    for (entity, mut cooldown, mut ammo, target) in tower_query.iter_mut() {
+    for (mut cooldown, mut ammo, target, stats, build_order, tower_transform) in
+        tower_query.iter_mut()
+    {
         cooldown.0.tick(time.delta());
         if !cooldown.0.just_finished() {
             continue;
         }
 
-        if target.0.is_none() {
+        let Some(target_transform) = target.0.and_then(|e| enemy_query.get(e).ok()) else {
             continue;
-        }
+        };
 
         if ammo.current == 0 {
             continue;
         }
 
-        let Some(head) = children_query
-            .iter_descendants(entity)
-            .find_map(|descendant| tower_head_query.get(descendant).ok())
-        else {
-            warn!("headless tower?");
-            continue;
-        };
-
-        let (scale, rotation, translation) = head.to_scale_rotation_translation();
-        let laser_transform = Transform {
-            scale,
-            rotation,
-            translation: translation + rotation.mul_vec3(offset),
-        };
+        // The model's head turns towards the target over a few frames, but shots leave
+        // from where it ends up so that they don't depend on the frame rate.
+        let rotation = aim(tower_transform.translation, target_transform.translation);
+        let laser_transform = Transform::from_translation(
+            tower_transform.translation + HEAD_OFFSET + rotation.mul_vec3(MUZZLE_OFFSET),
+        )
+        .with_rotation(rotation);
 
         ammo.current = ammo.current.saturating_sub(1);
+        run_stats.lasers_fired += 1;
+
+        let crit = stats.crit_chance > 0. && rng.gen::<f32>() < stats.crit_chance;
 
         commands.spawn((
             Laser,
             Name::new("Laser"),
-            Mesh3d(meshes.add(Cuboid::new(0.1, 0.1, 0.1))),
-            MeshMaterial3d(material.0.clone()),
             laser_transform,
             Target(target.0),
+            Projectile {
+                tower: *build_order,
+                damage: stats.damage,
+                damage_kind: stats.damage_kind,
+                crit,
+                speed: stats.projectile_speed,
+                splash_radius: stats.splash_radius,
+                effects: stats.effects.clone(),
+            },
             DespawnOnReset,
         ));
     }
 }
 
+fn spawn_laser_model(
+    mut commands: Commands,
+    query: Query<Entity, Added<Laser>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    material: Res<LaserMaterial>,
+) {
+    for entity in query.iter() {
+        commands.entity(entity).insert((
+            Mesh3d(meshes.add(Cuboid::new(0.1, 0.1, 0.1))),
+            MeshMaterial3d(material.0.clone()),
+        ));
+    }
+}
+
 fn laser_movement(
     mut commands: Commands,
-    mut query: Query<(Entity, &mut Transform, &Target), With<Laser>>,
-    mut enemy_query: Query<(&mut HitPoints, &Transform), Without<Laser>>,
+    mut query: Query<(Entity, &mut Transform, &Target, &Projectile), With<Laser>>,
+    enemy_query: Query<(Entity, &Transform), (With<Enemy>, Without<Laser>)>,
     time: Res<Time>,
+    mut run_stats: ResMut<RunStats>,
+    mut damage_events: EventWriter<Damage>,
 ) {
-    for (laser_entity, mut transform, target) in query.iter_mut() {
+    for (laser_entity, mut transform, target, projectile) in query.iter_mut() {
         let Some(target_entity) = target.0 else {
             continue;
         };
 
-        if let Ok((mut hp, enemy)) = enemy_query.get_mut(target_entity) {
-            let diff = enemy.translation - transform.translation;
-            let dist = diff.length();
-            let step = time.delta_secs() * 8.;
+        let Ok((_, enemy)) = enemy_query.get(target_entity) else {
+            commands.entity(laser_entity).despawn_recursive();
+            continue;
+        };
+
+        let impact = enemy.translation;
+        let diff = impact - transform.translation;
+        let dist = diff.length();
+        let step = time.delta_secs() * projectile.speed;
+
+        if dist > step {
+            transform.translation += step * diff.normalize();
+            continue;
+        }
 
-            if dist > step {
-                transform.translation += step * diff.normalize();
-            } else {
-                hp.current = hp.current.saturating_sub(1);
-                commands.entity(laser_entity).despawn_recursive();
+        commands.entity(laser_entity).despawn_recursive();
+        run_stats.lasers_hit += 1;
+
+        for (enemy_entity, enemy) in enemy_query.iter() {
+            let hit = match projectile.splash_radius {
+                Some(radius) => enemy.translation.distance(impact) <= radius,
+                None => enemy_entity == target_entity,
+            };
+            if !hit {
+                continue;
             }
-        } else {
-            commands.entity(laser_entity).despawn_recursive();
+
+            damage_events.send(Damage {
+                target: enemy_entity,
+                amount: projectile.damage,
+                kind: projectile.damage_kind,
+                source: Some(projectile.tower),
+                crit: projectile.crit,
+                effects: projectile.effects.clone(),
+            });
         }
     }
 }
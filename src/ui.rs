@@ -3,12 +3,16 @@ use bevy_alt_ui_navigation_lite::prelude::*;
 use bevy_dolly::system::DollyUpdateSet;
 
 use crate::{
+    camera::MainCamera,
+    currency::{price, Currency},
+    enemy::{Enemy, HitPoints},
+    lives::Lives,
     loading::{Fonts, Images},
     map::ItemSpawner,
-    settings::DifficultySetting,
-    tower::Ammo,
+    settings::HealthBarSetting,
+    tower::{Ammo, TargetPriority, TowerLevel},
     waves::{WaveState, Waves},
-    DespawnOnReset, GameState, Lives, MainCamera,
+    DespawnOnReset, GameState,
 };
 
 pub const FOCUSED_BUTTON: Srgba = Srgba::rgb(0.25, 0.0, 0.25);
@@ -17,14 +21,19 @@ pub const NORMAL_BUTTON: Srgba = Srgba::rgb(0.15, 0.15, 0.15);
 pub const HOVERED_BUTTON: Srgba = Srgba::rgb(0.25, 0.25, 0.25);
 pub const PRESSED_BUTTON: Srgba = Srgba::rgb(0.35, 0.75, 0.35);
 pub const BUTTON_TEXT: Srgba = Srgba::rgb(0.9, 0.9, 0.9);
+pub const LOCKED_BUTTON_TEXT: Srgba = Srgba::rgb(0.4, 0.4, 0.4);
 pub const TITLE_TEXT: Srgba = bevy::color::palettes::css::DEEP_PINK;
 pub const UI_TEXT: Srgba = bevy::color::palettes::css::DEEP_PINK;
 pub const ALT_TEXT: Srgba = Srgba::rgb(0.9, 0.9, 0.9);
+pub const WARNING_TEXT: Srgba = bevy::color::palettes::css::RED;
 pub const CONTAINER_BACKGROUND: Srgba = Srgba::rgb(0.1, 0.1, 0.1);
 pub const OVERLAY: Srgba = Srgba::new(0.0, 0.0, 0.0, 0.6);
 pub const AMMO: Srgba = bevy::color::palettes::css::YELLOW;
 pub const AMMO_EMPTY: Srgba = bevy::color::palettes::css::RED;
 pub const SPAWNER_TIMER: Srgba = bevy::color::palettes::css::YELLOW;
+pub const TOWER_LEVEL: Srgba = Srgba::rgb(0.9, 0.9, 0.9);
+pub const HEALTH_FULL: Srgba = bevy::color::palettes::css::LIME;
+pub const HEALTH_EMPTY: Srgba = bevy::color::palettes::css::RED;
 
 #[derive(Component)]
 pub struct FollowInWorld(Entity);
@@ -32,9 +41,24 @@ pub struct FollowInWorld(Entity);
 #[derive(Component)]
 pub struct AmmoText(Entity);
 
+#[derive(Component)]
+pub struct TowerLevelText(Entity);
+
+#[derive(Component)]
+pub struct TargetPriorityText(Entity);
+
 #[derive(Component)]
 pub struct ItemSpawnerText(Entity);
 
+#[derive(Component)]
+pub struct HealthBar(Entity);
+
+#[derive(Component)]
+pub struct HealthBarFill(Entity);
+
+#[derive(Component)]
+pub struct HealthBarText(Entity);
+
 #[derive(Component)]
 pub struct WaveText;
 
@@ -47,6 +71,9 @@ pub struct WaveStatsText;
 #[derive(Component)]
 pub struct LivesContainer;
 
+#[derive(Component)]
+pub struct CurrencyText;
+
 pub struct UiPlugin;
 impl Plugin for UiPlugin {
     fn build(&self, app: &mut App) {
@@ -58,9 +85,16 @@ impl Plugin for UiPlugin {
                 update_wave_stats,
                 update_ammo,
                 spawn_ammo,
+                update_tower_level,
+                update_target_priority,
                 update_item_spawners,
                 spawn_item_spawners,
                 update_lives,
+                update_currency,
+                spawn_health_bars,
+                update_health_bars,
+                show_health_bars,
+                despawn_health_bars,
             )
                 .distributive_run_if(in_state(GameState::Playing)),
         )
@@ -71,8 +105,8 @@ impl Plugin for UiPlugin {
                 .before(UiSystem::Layout)
                 .run_if(in_state(GameState::Playing)),
         )
-        .add_systems(OnExit(GameState::MainMenu), setup)
-        .add_systems(OnExit(GameState::MainMenu), setup_lives);
+        .add_systems(OnEnter(GameState::Playing), setup)
+        .add_systems(OnEnter(GameState::Playing), setup_lives);
     }
 }
 
@@ -99,7 +133,7 @@ fn setup(mut commands: Commands, fonts: Res<Fonts>) {
             Name::new("WaveInfoContainer"),
             Node {
                 flex_direction: FlexDirection::Column,
-                width: Val::Px(165.),
+                width: Val::Px(230.),
                 position_type: PositionType::Absolute,
                 top: Val::Px(0.0),
                 right: Val::Px(0.0),
@@ -151,16 +185,26 @@ fn setup(mut commands: Commands, fonts: Res<Fonts>) {
                 ))
                 .with_children(|parent| {
                     parent.spawn((Text::new("STATS:"), text_style.clone()));
-                    parent
-                        .spawn((WaveStatsText, Text::new("?"), text_style_alt.clone()))
-                        .with_child((TextSpan::new("x "), text_style.clone()))
-                        .with_child((TextSpan::new("?"), text_style_alt.clone()))
-                        .with_child((TextSpan::new("HP"), text_style.clone()));
+                    // One line per group of enemies, filled in by `update_wave_stats`.
+                    parent.spawn((
+                        WaveStatsText,
+                        Node {
+                            flex_direction: FlexDirection::Column,
+                            align_items: AlignItems::FlexEnd,
+                            ..default()
+                        },
+                    ));
                 });
         });
 }
 
-fn setup_lives(mut commands: Commands, lives: Res<Lives>, images: Res<Images>) {
+fn setup_lives(
+    mut commands: Commands,
+    lives: Res<Lives>,
+    currency: Res<Currency>,
+    images: Res<Images>,
+    fonts: Res<Fonts>,
+) {
     commands
         .spawn((
             LivesContainer,
@@ -170,6 +214,7 @@ fn setup_lives(mut commands: Commands, lives: Res<Lives>, images: Res<Images>) {
                 top: Val::Px(0.0),
                 left: Val::Px(0.0),
                 padding: UiRect::all(Val::Px(5.)),
+                align_items: AlignItems::Center,
                 ..default()
             },
             BackgroundColor(OVERLAY.into()),
@@ -192,13 +237,28 @@ fn setup_lives(mut commands: Commands, lives: Res<Lives>, images: Res<Images>) {
                     },
                 ));
             }
+
+            parent.spawn((
+                CurrencyText,
+                Text::new(format!("${}", currency.0)),
+                TextFont {
+                    font: fonts.main.clone(),
+                    font_size: 16.,
+                    ..default()
+                },
+                TextColor(ALT_TEXT.into()),
+                Node {
+                    margin: UiRect::left(Val::Px(10.0)),
+                    ..default()
+                },
+            ));
         });
 }
 
 fn update_lives(
     lives: Res<Lives>,
     container_query: Query<&Children, With<LivesContainer>>,
-    mut image_query: Query<&mut Node>,
+    mut image_query: Query<&mut Node, With<ImageNode>>,
 ) {
     if !lives.is_changed() {
         return;
@@ -220,6 +280,53 @@ fn update_lives(
     }
 }
 
+fn update_currency(currency: Res<Currency>, mut query: Query<&mut Text, With<CurrencyText>>) {
+    if !currency.is_changed() {
+        return;
+    }
+
+    for mut text in query.iter_mut() {
+        text.0 = format!("${}", currency.0);
+    }
+}
+
+/// Spawns columns listing each action, with its gamepad and keyboard prompts.
+pub fn controls(parent: &mut ChildBuilder, fonts: &Fonts) {
+    parent.spawn((
+        Text::new("MOVE\nJUMP\nINTERACT\nPAUSE"),
+        TextFont {
+            font: fonts.main.clone(),
+            font_size: 20.0,
+            ..default()
+        },
+        TextColor(UI_TEXT.into()),
+        TextLayout::new_with_justify(JustifyText::Right),
+    ));
+    parent.spawn((
+        Text::new("\u{21CB}\u{21CE}\n\u{21A7}\n\u{21A4}\n\u{21FB}"),
+        TextFont {
+            font: fonts.prompts.clone(),
+            font_size: 20.0,
+            ..default()
+        },
+        TextColor(ALT_TEXT.into()),
+    ));
+    parent.spawn((
+        Text::new(concat!(
+            "\u{FF37}\u{FF21}\u{FF33}\u{FF24}\u{23F6}\u{23F4}\u{23F5}\u{23F7}\n",
+            "SPACE\n",
+            "\u{FF32}\n",
+            "\u{242F}"
+        )),
+        TextFont {
+            font: fonts.prompts.clone(),
+            font_size: 20.0,
+            ..default()
+        },
+        TextColor(ALT_TEXT.into()),
+    ));
+}
+
 pub fn buttons(
     mut interaction_query: Query<
         (&Interaction, &Focusable, &mut BackgroundColor),
@@ -251,18 +358,19 @@ pub fn buttons(
 
 fn spawn_ammo(
     mut commands: Commands,
-    query: Query<(Entity, &Ammo), Added<Ammo>>,
+    query: Query<(Entity, &Ammo, &TowerLevel, &TargetPriority), Added<Ammo>>,
     fonts: Res<Fonts>,
 ) {
-    for (entity, ammo) in query.iter() {
+    for (entity, ammo, level, priority) in query.iter() {
         commands
             .spawn((
                 Name::new("AmmoDisplay"),
                 Node {
                     position_type: PositionType::Absolute,
-                    width: Val::Px(100.),
+                    width: Val::Px(200.),
                     height: Val::Px(20.),
                     justify_content: JustifyContent::Center,
+                    column_gap: Val::Px(8.),
                     ..default()
                 },
                 GlobalZIndex(-1),
@@ -271,6 +379,16 @@ fn spawn_ammo(
                 DespawnOnReset,
             ))
             .with_children(|parent| {
+                parent.spawn((
+                    TowerLevelText(entity),
+                    Text::new(format!("LV{}", level.0)),
+                    TextFont {
+                        font: fonts.main.clone(),
+                        font_size: 16.,
+                        ..default()
+                    },
+                    TextColor(TOWER_LEVEL.into()),
+                ));
                 parent.spawn((
                     AmmoText(entity),
                     Text::new(format!("{}/{}", ammo.current, ammo.max)),
@@ -281,6 +399,16 @@ fn spawn_ammo(
                     },
                     TextColor(AMMO.into()),
                 ));
+                parent.spawn((
+                    TargetPriorityText(entity),
+                    Text::new(priority.to_string()),
+                    TextFont {
+                        font: fonts.main.clone(),
+                        font_size: 16.,
+                        ..default()
+                    },
+                    TextColor(TOWER_LEVEL.into()),
+                ));
             });
     }
 }
@@ -304,6 +432,32 @@ fn update_ammo(
     }
 }
 
+fn update_tower_level(
+    mut query: Query<(&mut Text, &TowerLevelText)>,
+    level_query: Query<&TowerLevel, Changed<TowerLevel>>,
+) {
+    for (mut text, entity) in query.iter_mut() {
+        let Ok(level) = level_query.get(entity.0) else {
+            continue;
+        };
+
+        text.0 = format!("LV{}", level.0);
+    }
+}
+
+fn update_target_priority(
+    mut query: Query<(&mut Text, &TargetPriorityText)>,
+    priority_query: Query<&TargetPriority, Changed<TargetPriority>>,
+) {
+    for (mut text, entity) in query.iter_mut() {
+        let Ok(priority) = priority_query.get(entity.0) else {
+            continue;
+        };
+
+        text.0 = priority.to_string();
+    }
+}
+
 fn spawn_item_spawners(
     mut commands: Commands,
     query: Query<Entity, Added<ItemSpawner>>,
@@ -348,18 +502,148 @@ fn update_item_spawners(
             continue;
         };
 
-        if item_spawner.timer.remaining() == Duration::ZERO && item_spawner.spawned == 1 {
+        if item_spawner.timer.remaining() == Duration::ZERO && item_spawner.returning > 0 {
+            text.0 = format!("{} +{}", item_spawner.item, item_spawner.returning);
+            *visibility = Visibility::Inherited;
+        } else if item_spawner.timer.remaining() == Duration::ZERO && item_spawner.spawned == 1 {
             text.0 = format!("{}", item_spawner.item);
             *visibility = Visibility::Inherited;
         } else if item_spawner.timer.remaining() == Duration::ZERO {
             *visibility = Visibility::Hidden;
         } else {
-            text.0 = format!("0:{:0>2.0}", item_spawner.timer.remaining_secs());
+            text.0 = format!(
+                "0:{:0>2.0} ${}",
+                item_spawner.timer.remaining_secs(),
+                price(item_spawner.item)
+            );
             *visibility = Visibility::Inherited;
         }
     }
 }
 
+fn spawn_health_bars(
+    mut commands: Commands,
+    query: Query<(Entity, &HitPoints), Added<Enemy>>,
+    fonts: Res<Fonts>,
+) {
+    for (entity, hp) in query.iter() {
+        commands
+            .spawn((
+                Name::new("HealthBar"),
+                HealthBar(entity),
+                Node {
+                    position_type: PositionType::Absolute,
+                    width: Val::Px(60.),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                GlobalZIndex(-1),
+                Visibility::Hidden,
+                FollowInWorld(entity),
+                DespawnOnReset,
+            ))
+            .with_children(|parent| {
+                parent.spawn((
+                    HealthBarText(entity),
+                    Text::new(format!("{}/{}", hp.current, hp.max)),
+                    TextFont {
+                        font: fonts.main.clone(),
+                        font_size: 12.,
+                        ..default()
+                    },
+                    TextColor(HEALTH_FULL.into()),
+                ));
+                parent
+                    .spawn((
+                        Node {
+                            width: Val::Percent(100.),
+                            height: Val::Px(4.),
+                            ..default()
+                        },
+                        BackgroundColor(OVERLAY.into()),
+                    ))
+                    .with_child((
+                        HealthBarFill(entity),
+                        Node {
+                            width: Val::Percent(100.),
+                            height: Val::Percent(100.),
+                            ..default()
+                        },
+                        BackgroundColor(HEALTH_FULL.into()),
+                    ));
+            });
+    }
+}
+
+fn update_health_bars(
+    mut text_query: Query<(&mut Text, &mut TextColor, &HealthBarText)>,
+    mut fill_query: Query<(&mut Node, &mut BackgroundColor, &HealthBarFill)>,
+    hp_query: Query<&HitPoints, Changed<HitPoints>>,
+) {
+    for (mut text, mut text_color, entity) in text_query.iter_mut() {
+        let Ok(hp) = hp_query.get(entity.0) else {
+            continue;
+        };
+
+        text.0 = format!("{}/{}", hp.current, hp.max);
+        text_color.0 = health_color(hp).into();
+    }
+
+    for (mut node, mut color, entity) in fill_query.iter_mut() {
+        let Ok(hp) = hp_query.get(entity.0) else {
+            continue;
+        };
+
+        node.width = Val::Percent(health_fraction(hp) * 100.);
+        color.0 = health_color(hp).into();
+    }
+}
+
+fn show_health_bars(
+    mut query: Query<(&mut Visibility, &HealthBar)>,
+    hp_query: Query<&HitPoints>,
+    setting: Res<HealthBarSetting>,
+) {
+    for (mut visibility, entity) in query.iter_mut() {
+        let Ok(hp) = hp_query.get(entity.0) else {
+            continue;
+        };
+
+        let visible = match *setting {
+            HealthBarSetting::Always => true,
+            HealthBarSetting::Damaged => hp.current < hp.max,
+            HealthBarSetting::Never => false,
+        };
+
+        visibility.set_if_neq(if visible {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        });
+    }
+}
+
+fn despawn_health_bars(
+    mut commands: Commands,
+    query: Query<(Entity, &HealthBar)>,
+    enemy_query: Query<(), With<Enemy>>,
+) {
+    for (entity, health_bar) in query.iter() {
+        if !enemy_query.contains(health_bar.0) {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+fn health_fraction(hp: &HitPoints) -> f32 {
+    hp.current as f32 / hp.max.max(1) as f32
+}
+
+fn health_color(hp: &HitPoints) -> Srgba {
+    HEALTH_EMPTY.mix(&HEALTH_FULL, health_fraction(hp))
+}
+
 fn follow(
     mut query: Query<(&mut Node, &FollowInWorld)>,
     world_query: Query<&GlobalTransform>,
@@ -399,7 +683,15 @@ fn update_waves(query: Query<Entity, With<WaveText>>, waves: Res<Waves>, mut wri
         };
 
         *writer.text(text, 0) = format!("{}", wave);
-        *writer.text(text, 2) = format!("{}", waves.waves.len());
+
+        // There's no last wave in endless mode
+        if waves.endless {
+            writer.text(text, 1).clear();
+            writer.text(text, 2).clear();
+        } else {
+            *writer.text(text, 1) = "/".to_string();
+            *writer.text(text, 2) = format!("{}", waves.waves.len());
+        }
     }
 }
 
@@ -424,23 +716,55 @@ fn update_wave_timer(
 }
 
 fn update_wave_stats(
+    mut commands: Commands,
     query: Query<Entity, With<WaveStatsText>>,
     waves: Res<Waves>,
-    difficulty: Res<DifficultySetting>,
-    mut writer: TextUiWriter,
+    fonts: Res<Fonts>,
 ) {
+    if !waves.is_changed() {
+        return;
+    }
+
     let Some(current) = waves.current() else {
         return;
     };
 
-    let extra_hp = match *difficulty {
-        DifficultySetting::Normal => 0,
-        DifficultySetting::Hard => 1,
-        DifficultySetting::Extra => 2,
-    };
+    let text_style = (
+        TextFont {
+            font: fonts.main.clone(),
+            font_size: 16.,
+            ..default()
+        },
+        TextColor(UI_TEXT.into()),
+    );
+    let text_style_alt = (
+        TextFont {
+            font: fonts.main.clone(),
+            font_size: 16.,
+            ..default()
+        },
+        TextColor(ALT_TEXT.into()),
+    );
 
-    for text in &query {
-        *writer.text(text, 0) = format!("{}", current.num);
-        *writer.text(text, 2) = format!("{}", current.hp + extra_hp);
+    for entity in &query {
+        commands
+            .entity(entity)
+            .despawn_descendants()
+            .with_children(|parent| {
+                for group in &current.groups {
+                    parent
+                        .spawn((Text::new(format!("{}", group.num)), text_style_alt.clone()))
+                        .with_child((TextSpan::new("x "), text_style.clone()))
+                        .with_child((
+                            TextSpan::new(format!("{} ", group.kind)),
+                            text_style_alt.clone(),
+                        ))
+                        .with_child((
+                            TextSpan::new(format!("{}", group.kind.hp(group.hp))),
+                            text_style_alt.clone(),
+                        ))
+                        .with_child((TextSpan::new("HP"), text_style.clone()));
+                }
+            });
     }
 }
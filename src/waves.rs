@@ -1,77 +1,184 @@
-use bevy::prelude::*;
+use std::fmt::Display;
 
-use crate::{enemy::SpawnEnemyEvent, settings::DifficultySetting, GameState};
+use bevy::{
+    asset::{io::Reader, AssetLoader, LoadContext},
+    prelude::*,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    enemy::{EnemyKind, SpawnEnemyEvent},
+    settings::DifficultySetting,
+    GameState,
+};
+
+/// Seconds between waves in endless mode.
+const ENDLESS_DELAY: f32 = 20.;
+/// The most enemies in a single group in endless mode.
+const ENDLESS_MAX_NUM: usize = 40;
+/// The shortest interval between enemies in endless mode.
+const ENDLESS_MIN_INTERVAL: f32 = 0.5;
 
 pub struct WavePlugin;
 
+/// Whether the run ends after the last wave of the level, or keeps going.
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GameMode {
+    #[default]
+    Campaign,
+    /// More waves are generated after the level's own, each harder than the last, until
+    /// the player runs out of lives.
+    Endless,
+}
+
 impl Plugin for WavePlugin {
     fn build(&self, app: &mut App) {
-        let mut waves = Waves::default();
-        waves.waves.push(Wave {
-            delay: 15.,
-            num: 4,
-            interval: 4.,
-            hp: 2,
-        });
-        waves.waves.push(Wave {
-            delay: 30.,
-            num: 8,
-            interval: 4.,
-            hp: 2,
-        });
-        waves.waves.push(Wave {
-            delay: 30.,
-            num: 4,
-            interval: 4.,
-            hp: 6,
-        });
-        waves.waves.push(Wave {
-            delay: 30.,
-            num: 8,
-            interval: 4.,
-            hp: 4,
-        });
-        waves.waves.push(Wave {
-            delay: 30.,
-            num: 4,
-            interval: 4.,
-            hp: 10,
-        });
-        waves.waves.push(Wave {
-            delay: 30.,
-            num: 8,
-            interval: 4.,
-            hp: 6,
-        });
-        waves.waves.push(Wave {
-            delay: 30.,
-            num: 4,
-            interval: 4.,
-            hp: 14,
-        });
-        waves.waves.push(Wave {
-            delay: 30.,
-            num: 8,
-            interval: 4.,
-            hp: 8,
-        });
-        waves.waves.push(Wave {
-            delay: 30.,
-            num: 4,
-            interval: 4.,
-            hp: 18,
-        });
-        waves.waves.push(Wave {
-            delay: 30.,
-            num: 8,
-            interval: 4.,
-            hp: 10,
-        });
-        app.insert_resource(WaveState::from(&waves.waves[0]))
-            .insert_resource(waves);
-
-        app.add_systems(Update, spawn_enemies.run_if(in_state(GameState::Playing)))
-            .add_systems(OnExit(GameState::GameOver), reset);
+        app.init_resource::<GameMode>()
+            .init_asset::<WaveList>()
+            .init_asset_loader::<WaveListLoader>()
+            .add_systems(OnEnter(GameState::Playing), setup)
+            .add_systems(
+                FixedUpdate,
+                spawn_enemies.run_if(in_state(GameState::Playing)),
+            );
+    }
+}
+
+/// The waves for a level, loaded from a `.waves.ron` file.
+///
+/// The waves for the level being played are also inserted as a resource.
+#[derive(Asset, Resource, TypePath, Deserialize, Clone, Debug)]
+pub struct WaveList {
+    pub normal: Vec<Wave>,
+    /// Waves to use on `DifficultySetting::Hard`. If omitted, the normal waves are used
+    /// with one extra hit point per enemy.
+    #[serde(default)]
+    pub hard: Option<Vec<Wave>>,
+    /// Waves to use on `DifficultySetting::Extra`. If omitted, the normal waves are used
+    /// with two extra hit points per enemy.
+    #[serde(default)]
+    pub extra: Option<Vec<Wave>>,
+}
+impl WaveList {
+    /// Returns the waves to play on the given difficulty.
+    pub fn waves(&self, difficulty: &DifficultySetting) -> Vec<Wave> {
+        let (waves, extra_hp) = match difficulty {
+            DifficultySetting::Normal => (None, 0),
+            DifficultySetting::Hard => (self.hard.as_ref(), 1),
+            DifficultySetting::Extra => (self.extra.as_ref(), 2),
+        };
+
+        match waves {
+            Some(waves) => waves.clone(),
+            None => self
+                .normal
+                .iter()
+                .map(|wave| Wave {
+                    groups: wave
+                        .groups
+                        .iter()
+                        .map(|group| SpawnGroup {
+                            hp: group.hp + extra_hp,
+                            ..group.clone()
+                        })
+                        .collect(),
+                    ..wave.clone()
+                })
+                .collect(),
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        let lists = [
+            ("normal", Some(&self.normal)),
+            ("hard", self.hard.as_ref()),
+            ("extra", self.extra.as_ref()),
+        ];
+
+        for (name, waves) in lists {
+            let Some(waves) = waves else {
+                continue;
+            };
+
+            if waves.is_empty() {
+                return Err(format!("{} must have at least one wave", name));
+            }
+
+            for (i, wave) in waves.iter().enumerate() {
+                if wave.groups.is_empty() {
+                    return Err(format!("{} wave {} has no groups", name, i + 1));
+                }
+                if wave.delay < 0. {
+                    return Err(format!("{} wave {} has a negative delay", name, i + 1));
+                }
+
+                for (j, group) in wave.groups.iter().enumerate() {
+                    let group_name = format!("{} wave {} group {}", name, i + 1, j + 1);
+
+                    if group.num == 0 {
+                        return Err(format!("{} has no enemies", group_name));
+                    }
+                    if group.hp == 0 {
+                        return Err(format!("{} has enemies with no hp", group_name));
+                    }
+                    if group.interval <= 0. {
+                        return Err(format!("{} must have a positive interval", group_name));
+                    }
+                    if group.delay < 0. {
+                        return Err(format!("{} has a negative delay", group_name));
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub enum WaveListLoaderError {
+    Io(std::io::Error),
+    Ron(ron::error::SpannedError),
+    Invalid(String),
+}
+impl Display for WaveListLoaderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "Could not read waves: {}", e),
+            Self::Ron(e) => write!(f, "Could not parse waves: {}", e),
+            Self::Invalid(e) => write!(f, "Invalid waves: {}", e),
+        }
+    }
+}
+impl std::error::Error for WaveListLoaderError {}
+
+#[derive(Default)]
+pub struct WaveListLoader;
+impl AssetLoader for WaveListLoader {
+    type Asset = WaveList;
+    type Settings = ();
+    type Error = WaveListLoaderError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader
+            .read_to_end(&mut bytes)
+            .await
+            .map_err(WaveListLoaderError::Io)?;
+
+        let wave_list: WaveList = ron::de::from_bytes(&bytes).map_err(WaveListLoaderError::Ron)?;
+        wave_list.validate().map_err(WaveListLoaderError::Invalid)?;
+
+        Ok(wave_list)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["waves.ron"]
     }
 }
 
@@ -79,6 +186,8 @@ impl Plugin for WavePlugin {
 pub struct Waves {
     pub waves: Vec<Wave>,
     pub current: usize,
+    /// Whether waves are generated after the last one, forever.
+    pub endless: bool,
 }
 impl Waves {
     pub fn current(&self) -> Option<&Wave> {
@@ -86,31 +195,129 @@ impl Waves {
     }
     pub fn advance(&mut self) -> Option<&Wave> {
         self.current += 1;
+        self.generate();
         self.current()
     }
+
+    /// In endless mode, generates waves until there is one for `current`.
+    pub fn generate(&mut self) {
+        if !self.endless {
+            return;
+        }
+
+        while self.waves.len() <= self.current {
+            let Some(previous) = self.waves.last() else {
+                return;
+            };
+
+            let wave = previous.next_endless(self.waves.len());
+            self.waves.push(wave);
+        }
+    }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Deserialize)]
 pub struct Wave {
+    /// Seconds to wait before the wave starts.
+    pub delay: f32,
+    /// Groups of enemies, spawned one after the other.
+    pub groups: Vec<SpawnGroup>,
+}
+impl Wave {
+    /// Generates the wave after this one in endless mode, where `index` is the index of
+    /// the new wave. Each wave has more enemies with more hit points, arriving more
+    /// quickly, alongside a group of a different kind of enemy each time and a few tanks
+    /// every fifth wave.
+    pub fn next_endless(&self, index: usize) -> Self {
+        const MIX: [EnemyKind; 4] = [
+            EnemyKind::Fast,
+            EnemyKind::Armored,
+            EnemyKind::Swarm,
+            EnemyKind::Tank,
+        ];
+
+        let main = &self.groups[0];
+        let num = (main.num + 1).min(ENDLESS_MAX_NUM);
+        let hp = main.hp + main.hp.div_ceil(8);
+        let interval = (main.interval * 0.95).max(ENDLESS_MIN_INTERVAL);
+
+        let mut groups = vec![
+            SpawnGroup {
+                kind: EnemyKind::Basic,
+                num,
+                hp,
+                interval,
+                delay: 0.,
+            },
+            SpawnGroup {
+                kind: MIX[index % MIX.len()],
+                num: (num / 2).max(1),
+                hp,
+                interval,
+                delay: 2.,
+            },
+        ];
+
+        if index % 5 == 4 {
+            groups.push(SpawnGroup {
+                kind: EnemyKind::Tank,
+                num: 1 + index / 10,
+                hp: hp * 2,
+                interval: interval * 2.,
+                delay: 2.,
+            });
+        }
+
+        Self {
+            delay: ENDLESS_DELAY,
+            groups,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct SpawnGroup {
+    #[serde(default)]
+    pub kind: EnemyKind,
     pub num: usize,
     pub hp: u32,
+    /// Seconds between each enemy in the group.
     pub interval: f32,
+    /// Seconds to wait after the previous group before this one starts.
+    #[serde(default)]
     pub delay: f32,
 }
 
 #[derive(Resource)]
 pub struct WaveState {
     pub delay_timer: Timer,
+    pub group_delay_timer: Timer,
     pub spawn_timer: Timer,
+    /// Index of the current group in `Wave::groups`.
+    pub group: usize,
+    /// Enemies remaining in the current group.
     pub remaining: usize,
 }
+impl WaveState {
+    fn start_group(&mut self, index: usize, group: &SpawnGroup) {
+        self.group = index;
+        self.group_delay_timer = Timer::from_seconds(group.delay, TimerMode::Once);
+        self.spawn_timer = Timer::from_seconds(group.interval, TimerMode::Repeating);
+        self.remaining = group.num;
+    }
+}
 
 impl From<&Wave> for WaveState {
     fn from(value: &Wave) -> Self {
+        let first = &value.groups[0];
+
         Self {
             delay_timer: Timer::from_seconds(value.delay, TimerMode::Once),
-            spawn_timer: Timer::from_seconds(value.interval, TimerMode::Repeating),
-            remaining: value.num,
+            // The first group starts along with the wave.
+            group_delay_timer: Timer::from_seconds(0., TimerMode::Once),
+            spawn_timer: Timer::from_seconds(first.interval, TimerMode::Repeating),
+            group: 0,
+            remaining: first.num,
         }
     }
 }
@@ -121,7 +328,6 @@ pub fn spawn_enemies(
     mut wave_state: ResMut<WaveState>,
     time: Res<Time>,
     mut events: EventWriter<SpawnEnemyEvent>,
-    difficulty: Res<DifficultySetting>,
 ) {
     let Some(current_wave) = waves.current() else {
         return;
@@ -132,31 +338,57 @@ pub fn spawn_enemies(
         return;
     }
 
+    wave_state.group_delay_timer.tick(time.delta());
+    if !wave_state.group_delay_timer.finished() {
+        return;
+    }
+
     wave_state.spawn_timer.tick(time.delta());
     if !wave_state.spawn_timer.just_finished() {
         return;
     }
 
-    let extra_hp = match *difficulty {
-        DifficultySetting::Normal => 0,
-        DifficultySetting::Hard => 1,
-        DifficultySetting::Extra => 2,
+    let Some(group) = current_wave.groups.get(wave_state.group) else {
+        // The current wave lost some groups when it was hot reloaded.
+        wave_state.remaining = 0;
+        if let Some(next) = waves.advance() {
+            commands.insert_resource(WaveState::from(next));
+        }
+        return;
     };
 
     events.send(SpawnEnemyEvent {
-        hp: current_wave.hp + extra_hp,
+        kind: group.kind,
+        hp: group.hp,
+        wave: waves.current,
     });
 
     wave_state.remaining -= 1;
 
-    if wave_state.remaining == 0 {
-        if let Some(next) = waves.advance() {
-            commands.insert_resource(WaveState::from(next));
-        }
+    if wave_state.remaining > 0 {
+        return;
+    }
+
+    let next_index = wave_state.group + 1;
+    if let Some(next_group) = current_wave.groups.get(next_index) {
+        wave_state.start_group(next_index, next_group);
+    } else if let Some(next) = waves.advance() {
+        commands.insert_resource(WaveState::from(next));
     }
 }
 
-fn reset(mut commands: Commands, mut waves: ResMut<Waves>) {
+fn setup(
+    mut commands: Commands,
+    wave_list: Res<WaveList>,
+    difficulty: Res<DifficultySetting>,
+    mode: Res<GameMode>,
+) {
+    let waves = Waves {
+        waves: wave_list.waves(&difficulty),
+        current: 0,
+        endless: *mode == GameMode::Endless,
+    };
+
     commands.insert_resource(WaveState::from(&waves.waves[0]));
-    waves.current = 0;
+    commands.insert_resource(waves);
 }
@@ -0,0 +1,367 @@
+use std::time::Duration;
+
+use bevy::{prelude::*, time::TimeUpdateStrategy};
+
+use undefended::{
+    currency::Currency,
+    enemy::{Enemy, EnemyKind, HitPoints, SpawnEnemyEvent},
+    headless::HeadlessPlugin,
+    level::Level,
+    lives::{Lives, Won},
+    map::{Floor, TilePos},
+    rng::GameSeed,
+    stats::RunStats,
+    tower::{Ammo, SpawnTowerEvent, Tower, TowerHead, TowerKind},
+    waves::{GameMode, SpawnGroup, Wave, WaveList, Waves},
+    GameState, TICK_RATE,
+};
+
+/// A straight path along the second row, with a strip of floor tiles beside it.
+fn level() -> Level {
+    Level {
+        name: "Test".to_string(),
+        size: UVec2::new(9, 5),
+        tiles: vec![
+            ".........".to_string(),
+            ".........".to_string(),
+            "####.....".to_string(),
+            ".........".to_string(),
+            ".........".to_string(),
+        ],
+        path: vec![UVec2::new(0, 1), UVec2::new(8, 1)],
+        player_start: UVec2::new(0, 2),
+        item_spawners: vec![],
+        moving_floors: vec![],
+    }
+}
+
+fn wave(delay: f32, num: usize, hp: u32) -> Wave {
+    Wave {
+        delay,
+        groups: vec![SpawnGroup {
+            kind: EnemyKind::Basic,
+            num,
+            hp,
+            interval: 1.,
+            delay: 0.,
+        }],
+    }
+}
+
+/// Waves that won't start for long enough that tests can spawn their own enemies.
+fn quiet_waves() -> WaveList {
+    WaveList {
+        normal: vec![wave(1000., 1, 1)],
+        hard: None,
+        extra: None,
+    }
+}
+
+fn app(wave_list: WaveList) -> App {
+    let mut app = App::new();
+
+    app.add_plugins(HeadlessPlugin::default())
+        .insert_resource(level())
+        .insert_resource(wave_list)
+        .insert_resource(GameSeed(Some(1234)));
+
+    // Spawn the map
+    app.update();
+
+    app
+}
+
+fn run(app: &mut App, secs: f32) {
+    for _ in 0..(secs as f64 * TICK_RATE).ceil() as usize {
+        app.update();
+    }
+}
+
+fn build_tower(app: &mut App, pos: UVec2, kind: TowerKind) {
+    let tile = app
+        .world_mut()
+        .query_filtered::<(Entity, &TilePos), With<Floor>>()
+        .iter(app.world())
+        .find(|(_, tile_pos)| tile_pos.0 == pos)
+        .map(|(entity, _)| entity)
+        .expect("no floor tile at that position");
+
+    app.world_mut().send_event(SpawnTowerEvent(tile, kind));
+    app.update();
+}
+
+fn spawn_enemy(app: &mut App, hp: u32) {
+    app.world_mut().send_event(SpawnEnemyEvent {
+        kind: EnemyKind::Basic,
+        hp,
+        wave: 0,
+    });
+    app.update();
+}
+
+fn enemy_hit_points(app: &mut App) -> Vec<u32> {
+    app.world_mut()
+        .query_filtered::<&HitPoints, With<Enemy>>()
+        .iter(app.world())
+        .map(|hp| hp.current)
+        .collect()
+}
+
+fn state(app: &App) -> GameState {
+    *app.world().resource::<State<GameState>>().get()
+}
+
+#[test]
+fn tower_damages_enemy_in_range() {
+    let mut app = app(quiet_waves());
+
+    build_tower(&mut app, UVec2::new(3, 2), TowerKind::Laser);
+    spawn_enemy(&mut app, 100);
+
+    run(&mut app, 10.);
+
+    let hit_points = enemy_hit_points(&mut app);
+    assert_eq!(hit_points.len(), 1);
+    assert!(hit_points[0] < 100);
+
+    let ammo = app.world_mut().query::<&Ammo>().single(app.world()).current;
+    assert!(ammo < TowerKind::Laser.stats().max_ammo);
+}
+
+#[test]
+fn tower_without_ammo_does_not_shoot() {
+    let mut app = app(quiet_waves());
+
+    build_tower(&mut app, UVec2::new(3, 2), TowerKind::Laser);
+
+    let mut ammo = app.world_mut().query::<&mut Ammo>();
+    ammo.single_mut(app.world_mut()).current = 0;
+
+    spawn_enemy(&mut app, 100);
+
+    run(&mut app, 10.);
+
+    assert_eq!(enemy_hit_points(&mut app), vec![100]);
+}
+
+#[test]
+fn enemy_reaching_goal_costs_lives() {
+    let mut app = app(quiet_waves());
+
+    spawn_enemy(&mut app, 1);
+
+    // The path is 16 world units long, and basic enemies move at 1 unit per second.
+    run(&mut app, 20.);
+
+    assert!(enemy_hit_points(&mut app).is_empty());
+    assert_eq!(app.world().resource::<Lives>().0, 2);
+    assert_eq!(state(&app), GameState::Playing);
+}
+
+#[test]
+fn losing_all_lives_loses_the_game() {
+    let mut app = app(quiet_waves());
+
+    for _ in 0..3 {
+        spawn_enemy(&mut app, 1);
+    }
+
+    run(&mut app, 20.);
+
+    assert_eq!(app.world().resource::<Lives>().0, 0);
+    assert_eq!(state(&app), GameState::GameOver);
+    assert!(!app.world().resource::<Won>().0);
+}
+
+#[test]
+fn killing_enemies_earns_currency() {
+    let mut app = app(quiet_waves());
+
+    build_tower(&mut app, UVec2::new(3, 2), TowerKind::Sniper);
+    spawn_enemy(&mut app, 1);
+
+    run(&mut app, 10.);
+
+    assert!(enemy_hit_points(&mut app).is_empty());
+    assert_eq!(app.world().resource::<Lives>().0, 3);
+    // One coin for its only hit point, and one for the kill.
+    assert_eq!(app.world().resource::<Currency>().0, 2);
+}
+
+#[test]
+fn run_stats_record_kills_leaks_and_damage() {
+    let mut app = app(quiet_waves());
+
+    build_tower(&mut app, UVec2::new(3, 2), TowerKind::Sniper);
+    spawn_enemy(&mut app, 1);
+    run(&mut app, 10.);
+
+    // Nothing can shoot an enemy once it's past the tower.
+    app.world_mut().send_event(SpawnEnemyEvent {
+        kind: EnemyKind::Basic,
+        hp: 1,
+        wave: 1,
+    });
+    let mut ammo = app.world_mut().query::<&mut Ammo>();
+    ammo.single_mut(app.world_mut()).current = 0;
+    run(&mut app, 20.);
+
+    let stats = app.world().resource::<RunStats>();
+    assert_eq!(stats.kills, 1);
+    assert_eq!(stats.waves.len(), 2);
+    assert_eq!((stats.waves[0].killed, stats.waves[0].leaked), (1, 0));
+    assert_eq!((stats.waves[1].killed, stats.waves[1].leaked), (0, 1));
+    assert_eq!(stats.towers.len(), 1);
+    assert_eq!(stats.towers[0].damage, 1);
+    assert!(stats.lasers_hit >= 1);
+    assert!(stats.lasers_fired >= stats.lasers_hit);
+}
+
+#[test]
+fn waves_advance_as_they_are_spawned() {
+    let mut app = app(WaveList {
+        normal: vec![wave(1., 1, 100), wave(5., 1, 100)],
+        hard: None,
+        extra: None,
+    });
+
+    assert_eq!(app.world().resource::<Waves>().current, 0);
+
+    // The first enemy spawns after the wave's delay, plus the group's interval.
+    run(&mut app, 2.5);
+    assert_eq!(app.world().resource::<Waves>().current, 1);
+    assert_eq!(enemy_hit_points(&mut app).len(), 1);
+
+    run(&mut app, 6.);
+    assert_eq!(app.world().resource::<Waves>().current, 2);
+    assert_eq!(enemy_hit_points(&mut app).len(), 2);
+}
+
+#[test]
+fn clearing_all_waves_wins_the_game() {
+    let mut app = app(WaveList {
+        normal: vec![wave(0., 2, 1)],
+        hard: None,
+        extra: None,
+    });
+
+    build_tower(&mut app, UVec2::new(3, 2), TowerKind::Sniper);
+
+    run(&mut app, 15.);
+
+    assert_eq!(app.world().resource::<Waves>().current, 1);
+    assert_eq!(app.world().resource::<Lives>().0, 3);
+    assert_eq!(state(&app), GameState::GameOver);
+    assert!(app.world().resource::<Won>().0);
+}
+
+#[test]
+fn same_seed_reproduces_run() {
+    let play = || {
+        let mut app = app(WaveList {
+            normal: vec![wave(0., 5, 20)],
+            hard: None,
+            extra: None,
+        });
+
+        build_tower(&mut app, UVec2::new(3, 2), TowerKind::Laser);
+
+        run(&mut app, 8.);
+
+        app.world_mut()
+            .query_filtered::<(&Transform, &HitPoints), With<Enemy>>()
+            .iter(app.world())
+            .map(|(transform, hp)| (transform.translation, hp.current))
+            .collect::<Vec<_>>()
+    };
+
+    let first = play();
+    assert!(!first.is_empty());
+    assert_eq!(first, play());
+}
+
+/// Turns tower heads a little every frame, like the model does while aiming.
+fn turn_heads(mut query: Query<&mut Transform, With<TowerHead>>, time: Res<Time>) {
+    for mut transform in query.iter_mut() {
+        transform.rotate_y(time.delta_secs() * 5.);
+    }
+}
+
+#[test]
+fn frame_pacing_does_not_change_run() {
+    let play = |frames: Vec<Duration>| {
+        let mut app = app(WaveList {
+            normal: vec![wave(0., 5, 20)],
+            hard: None,
+            extra: None,
+        });
+        app.add_systems(Update, turn_heads);
+
+        build_tower(&mut app, UVec2::new(3, 2), TowerKind::Laser);
+
+        let tower = app
+            .world_mut()
+            .query_filtered::<Entity, With<Tower>>()
+            .single(app.world());
+        app.world_mut().entity_mut(tower).with_children(|parent| {
+            parent.spawn((TowerHead, Transform::from_xyz(0., 1.5, 0.)));
+        });
+
+        for frame in frames {
+            app.insert_resource(TimeUpdateStrategy::ManualDuration(frame));
+            app.update();
+        }
+
+        let hit_points = app
+            .world_mut()
+            .query_filtered::<(&Transform, &HitPoints), With<Enemy>>()
+            .iter(app.world())
+            .map(|(transform, hp)| (transform.translation, hp.current))
+            .collect::<Vec<_>>();
+
+        (hit_points, app.world().resource::<RunStats>().lasers_hit)
+    };
+
+    let tick = Duration::from_secs_f64(1. / TICK_RATE);
+    let ticks = 480;
+
+    let steady = play(vec![tick; ticks]);
+    assert!(!steady.0.is_empty());
+    assert!(steady.1 > 0);
+
+    // Three ticks per frame.
+    assert_eq!(steady, play(vec![tick * 3; ticks / 3]));
+    // Frames that don't line up with ticks.
+    assert_eq!(steady, play([tick / 2, tick - tick / 2].repeat(ticks)));
+}
+
+#[test]
+fn endless_mode_keeps_generating_waves() {
+    let mut app = App::new();
+
+    app.add_plugins(HeadlessPlugin::default())
+        .insert_resource(level())
+        .insert_resource(WaveList {
+            normal: vec![wave(0., 1, 1)],
+            hard: None,
+            extra: None,
+        })
+        .insert_resource(GameSeed(Some(1234)))
+        .insert_resource(GameMode::Endless);
+    app.update();
+
+    build_tower(&mut app, UVec2::new(3, 2), TowerKind::Sniper);
+
+    // Clearing the only wave from the level doesn't end the run.
+    run(&mut app, 15.);
+    assert_eq!(state(&app), GameState::Playing);
+
+    let waves = app.world().resource::<Waves>();
+    assert!(waves.endless);
+    assert_eq!(waves.current, 1);
+    assert!(waves.waves.len() > 1);
+
+    let next = &waves.waves[1];
+    assert!(next.groups[0].hp > 1);
+    assert!(next.groups.len() > 1);
+}